@@ -0,0 +1,423 @@
+use crate::execution::ExecutionDevice;
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use std::collections::VecDeque;
+use std::fs;
+use std::future::Future;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+// PCM audio output, the program fills the sample buffer and rings AUDIO_PLAY to submit it.
+pub const AUDIO_ADDRESS: u32 = 0xFFFF0700;
+pub const AUDIO_BUFFER: u32 = 0xFFFF8000;
+pub const AUDIO_BUFFER_SIZE: u32 = 0x4000;
+
+// Register offsets from AUDIO_ADDRESS.
+// Samples per second, between AUDIO_MIN_RATE and AUDIO_MAX_RATE.
+const AUDIO_SAMPLE_RATE: u32 = 0x00;
+// 0 signed 16-bit little endian, 1 unsigned 8-bit. Always mono.
+const AUDIO_FORMAT: u32 = 0x04;
+// Bytes of the sample buffer to play, starting from AUDIO_BUFFER.
+const AUDIO_LENGTH: u32 = 0x08;
+// Write: writing the lowest byte submits the buffer, it can be reused right away.
+const AUDIO_PLAY: u32 = 0x0C;
+// Read: bits 0 to 7 hold the number of buffers waiting for the frontend, bit 31 is set
+// if a buffer was dropped. Any write clears the overflow bit.
+const AUDIO_STATUS: u32 = 0x10;
+const AUDIO_SIZE: u32 = 0x14;
+
+pub const AUDIO_MIN_RATE: u32 = 1000;
+pub const AUDIO_MAX_RATE: u32 = 96000;
+pub const AUDIO_DEFAULT_RATE: u32 = 8000;
+
+pub const AUDIO_QUEUE_SIZE: usize = 32;
+
+// Keeps a forgotten capture from eating all the memory, roughly 10 minutes at 44.1 kHz.
+pub const AUDIO_CAPTURE_MAX_SAMPLES: usize = 44100 * 60 * 10;
+
+const FORMAT_PCM16: u32 = 0;
+const FORMAT_PCM8: u32 = 1;
+
+const STATUS_OVERFLOW: u32 = 1 << 31;
+
+#[derive(Clone)]
+pub struct AudioClip {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+impl AudioClip {
+    // Mono 16-bit RIFF WAVE, can be handed to decodeAudioData as is.
+    pub fn encode_wav(&self) -> Vec<u8> {
+        let data_size = (self.samples.len() * 2) as u32;
+
+        let mut result = Vec::with_capacity(44 + data_size as usize);
+
+        result.extend_from_slice(b"RIFF");
+        result.extend_from_slice(&(36 + data_size).to_le_bytes());
+        result.extend_from_slice(b"WAVE");
+
+        result.extend_from_slice(b"fmt ");
+        result.extend_from_slice(&16u32.to_le_bytes()); // chunk size
+        result.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        result.extend_from_slice(&1u16.to_le_bytes()); // channels
+        result.extend_from_slice(&self.sample_rate.to_le_bytes());
+        result.extend_from_slice(&(self.sample_rate * 2).to_le_bytes()); // byte rate
+        result.extend_from_slice(&2u16.to_le_bytes()); // block align
+        result.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+
+        result.extend_from_slice(b"data");
+        result.extend_from_slice(&data_size.to_le_bytes());
+
+        for sample in &self.samples {
+            result.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        result
+    }
+}
+
+// Every submitted buffer back to back, at the rate of the first one.
+#[derive(Default)]
+pub struct AudioCapture {
+    clip: Option<AudioClip>,
+    dropped: bool,
+}
+
+pub type AudioCaptureBody = Arc<Mutex<AudioCapture>>;
+
+impl AudioCapture {
+    pub fn new() -> AudioCapture {
+        AudioCapture::default()
+    }
+
+    // True if samples were dropped because the capture got too long.
+    pub fn dropped(&self) -> bool {
+        self.dropped
+    }
+
+    fn push(&mut self, clip: &AudioClip) {
+        if self.clip.is_none() {
+            self.clip = Some(clip.clone());
+
+            return;
+        }
+
+        let Some(capture) = &mut self.clip else {
+            return;
+        };
+
+        // Nearest sample, buffers at other rates should be rare.
+        let count =
+            clip.samples.len() as u64 * capture.sample_rate as u64 / clip.sample_rate as u64;
+        let left = AUDIO_CAPTURE_MAX_SAMPLES.saturating_sub(capture.samples.len());
+
+        if count as usize > left {
+            self.dropped = true;
+        }
+
+        capture
+            .samples
+            .extend((0..(count as usize).min(left)).map(|i| {
+                let source = i as u64 * clip.sample_rate as u64 / capture.sample_rate as u64;
+
+                clip.samples[source as usize]
+            }));
+    }
+
+    pub fn encode_wav(&self) -> Option<Vec<u8>> {
+        self.clip.as_ref().map(|clip| clip.encode_wav())
+    }
+
+    // Headless save, None if nothing was played.
+    pub fn save(&self, path: &Path) -> Option<()> {
+        fs::write(path, self.encode_wav()?).ok()
+    }
+}
+
+// Records everything the device plays while run goes, then writes it to path as a WAV file.
+// Ok(false) if the program never played anything, nothing is written then.
+pub async fn capture_audio<Device: ExecutionDevice + ?Sized>(
+    device: &Device,
+    run: impl Future<Output = Result<(), String>>,
+    path: &Path,
+) -> Result<bool, String> {
+    device.start_audio_capture();
+
+    let result = run.await;
+
+    let capture = device
+        .take_audio_capture()
+        .ok_or_else(|| "The audio capture was lost.".to_string())?;
+
+    result?;
+
+    let capture = capture.lock().unwrap();
+
+    if capture.encode_wav().is_none() {
+        return Ok(false);
+    }
+
+    capture
+        .save(path)
+        .map(|_| true)
+        .ok_or_else(|| format!("Failed to write {}.", path.display()))
+}
+
+pub struct AudioState {
+    sample_rate: u32,
+    format: u32,
+    length: u32,
+    buffer: Vec<u8>,
+    clips: VecDeque<AudioClip>,
+    overflow: bool,
+    pub capture: Option<AudioCaptureBody>, // every submitted buffer is appended while set
+}
+
+pub struct AudioHandler {
+    pub state: Arc<Mutex<AudioState>>,
+}
+
+impl Default for AudioState {
+    fn default() -> AudioState {
+        AudioState::new()
+    }
+}
+
+impl AudioState {
+    pub fn new() -> AudioState {
+        AudioState {
+            sample_rate: AUDIO_DEFAULT_RATE,
+            format: FORMAT_PCM16,
+            length: 0,
+            buffer: vec![0; AUDIO_BUFFER_SIZE as usize],
+            clips: VecDeque::with_capacity(AUDIO_QUEUE_SIZE),
+            overflow: false,
+            capture: None,
+        }
+    }
+
+    // Oldest buffer the frontend has not played yet.
+    pub fn take_clip(&mut self) -> Option<AudioClip> {
+        self.clips.pop_front()
+    }
+
+    fn samples(&self) -> Vec<i16> {
+        let data = &self.buffer[..self.length.min(AUDIO_BUFFER_SIZE) as usize];
+
+        match self.format {
+            FORMAT_PCM8 => data
+                .iter()
+                .map(|sample| ((*sample as i16) - 128) << 8)
+                .collect(),
+            _ => data
+                .chunks_exact(2)
+                .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+                .collect(),
+        }
+    }
+
+    fn play(&mut self) {
+        let clip = AudioClip {
+            sample_rate: self.sample_rate.clamp(AUDIO_MIN_RATE, AUDIO_MAX_RATE),
+            samples: self.samples(),
+        };
+
+        if clip.samples.is_empty() {
+            return;
+        }
+
+        if let Some(capture) = &self.capture {
+            capture.lock().unwrap().push(&clip)
+        }
+
+        if self.clips.len() < AUDIO_QUEUE_SIZE {
+            self.clips.push_back(clip)
+        } else {
+            self.overflow = true
+        }
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            AUDIO_SAMPLE_RATE => self.sample_rate,
+            AUDIO_FORMAT => self.format,
+            AUDIO_LENGTH => self.length,
+            AUDIO_STATUS => {
+                let overflow = if self.overflow { STATUS_OVERFLOW } else { 0 };
+
+                overflow | self.clips.len() as u32
+            }
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, offset: u32, byte: u8) {
+        let register = match offset & !3 {
+            AUDIO_SAMPLE_RATE => &mut self.sample_rate,
+            AUDIO_FORMAT => &mut self.format,
+            AUDIO_LENGTH => &mut self.length,
+            // A sw writes four bytes, only ring once.
+            AUDIO_PLAY => {
+                if offset == AUDIO_PLAY {
+                    self.play()
+                }
+
+                return;
+            }
+            AUDIO_STATUS => {
+                self.overflow = false;
+
+                return;
+            }
+            _ => return,
+        };
+
+        *register = write_register_byte(*register, offset, byte);
+    }
+}
+
+impl AudioHandler {
+    pub fn new() -> AudioHandler {
+        AudioHandler {
+            state: Arc::new(Mutex::new(AudioState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (AUDIO_ADDRESS..AUDIO_ADDRESS + AUDIO_SIZE).contains(&address)
+            || (AUDIO_BUFFER..AUDIO_BUFFER + AUDIO_BUFFER_SIZE).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let state = self.state.lock().unwrap();
+
+        if address >= AUDIO_BUFFER {
+            return match state.buffer.get((address - AUDIO_BUFFER) as usize) {
+                Some(value) => Ok(*value),
+                None => Err(MemoryUnmapped(address)),
+            };
+        }
+
+        let offset = address.wrapping_sub(AUDIO_ADDRESS);
+
+        if offset >= AUDIO_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        Ok(read_register_byte(state.read_register(offset & !3), offset))
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if address >= AUDIO_BUFFER {
+            return match state.buffer.get_mut((address - AUDIO_BUFFER) as usize) {
+                Some(sample) => {
+                    *sample = value;
+
+                    Ok(())
+                }
+                None => Err(MemoryUnmapped(address)),
+            };
+        }
+
+        let offset = address.wrapping_sub(AUDIO_ADDRESS);
+
+        if offset >= AUDIO_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        state.write_register(offset, value);
+
+        Ok(())
+    }
+}
+
+impl MmioDevice for AudioHandler {
+    fn name(&self) -> &str {
+        "audio"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![
+            AUDIO_ADDRESS..AUDIO_ADDRESS + AUDIO_SIZE,
+            AUDIO_BUFFER..AUDIO_BUFFER + AUDIO_BUFFER_SIZE,
+        ]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        let mut state = self.state.lock().unwrap();
+
+        // The capture belongs to the host, it keeps going across resets.
+        let capture = state.capture.take();
+
+        *state = AudioState::new();
+        state.capture = capture;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::assemble_text;
+    use crate::golden::{golden_device, run_to_point, NoTime};
+    use crate::layout::LayoutPreset;
+    use futures::executor::block_on;
+    use std::collections::HashMap;
+
+    // Four PCM16 samples at the default rate, played once.
+    const PLAY: &str = "
+        lui $t1, 0xFFFF
+        ori $t2, $t1, 0x8000
+        li $t0, 0x7FFF0100
+        sw $t0, 0($t2)
+        sw $t0, 4($t2)
+        li $t0, 8
+        sw $t0, 0x708($t1)
+        sw $zero, 0x70C($t1)
+        li $v0, 10
+        syscall
+    ";
+
+    #[test]
+    fn capture_writes_what_the_program_played() {
+        let binary = assemble_text(PLAY, None).unwrap_or_else(|_| panic!("Failed to assemble"));
+        let layout = LayoutPreset::default().layout();
+        let device = golden_device(binary, &layout, Arc::new(NoTime)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("saturn-audio-{}.wav", std::process::id()));
+        let run = run_to_point(&device, None, HashMap::new());
+
+        assert_eq!(block_on(capture_audio(&device, run, &path)), Ok(true));
+
+        let wav = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        let word = |offset: usize| u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap());
+
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(word(24), AUDIO_DEFAULT_RATE);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(word(40), 8);
+
+        let samples: Vec<i16> = wav[44..]
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect();
+
+        assert_eq!(samples, vec![0x0100, 0x7FFF, 0x0100, 0x7FFF]);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::keyboard::{KeyboardHandler, KeyboardState, KEYBOARD_SELECTOR};
+use crate::keyboard::KEYBOARD_SELECTOR;
+use crate::layout::MemoryLayout;
+use crate::mmio::{MmioHandler, Peripherals};
+use crate::registry::{DeviceKind, MmioDevice};
 use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Cursor;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
 use titan::assembler::binary::{Binary, RegionFlags};
 use titan::assembler::line_details::LineDetails;
 use titan::assembler::string::{assemble_from, assemble_from_path, SourceError};
@@ -41,6 +43,19 @@ pub enum AssemblerResult {
     },
 }
 
+// Source line of every pc, for messages that name where something happened.
+pub fn source_lines(binary: &Binary, source: &str) -> HashMap<u32, usize> {
+    binary
+        .source_breakpoints(source, 0)
+        .into_iter()
+        .flat_map(|breakpoint| {
+            let line = breakpoint.line;
+
+            breakpoint.pcs.into_iter().map(move |pc| (pc, line))
+        })
+        .collect()
+}
+
 pub fn get_elf_finished_pcs(elf: &Elf) -> Vec<u32> {
     elf.program_headers
         .iter()
@@ -131,7 +146,7 @@ pub fn assemble_text(text: &str, path: Option<&str>) -> Result<Binary, SourceErr
 
 pub fn create_elf_state<Mem: Memory + Mountable>(
     elf: &Elf,
-    heap_size: u32,
+    layout: &MemoryLayout,
     mut memory: Mem,
 ) -> State<Mem> {
     for header in &elf.program_headers {
@@ -143,35 +158,65 @@ pub fn create_elf_state<Mem: Memory + Mountable>(
         memory.mount(region)
     }
 
-    let heap_end = 0x7FFFFFFCu32;
-
-    let heap = Region {
-        start: heap_end - heap_size,
-        data: vec![0; heap_size as usize],
-    };
-
-    memory.mount(heap);
-
     let mut state = State::new(elf.header.program_entry, memory);
-    state.registers.line[29] = heap_end;
+
+    layout.apply(&mut state);
 
     state
 }
 
-pub fn configure_keyboard(
-    memory: &mut SectionMemory<KeyboardHandler>,
-) -> Arc<Mutex<KeyboardState>> {
-    let handler = KeyboardHandler::new();
-    let keyboard = handler.state.clone();
+pub fn configure_peripherals(
+    memory: &mut SectionMemory<MmioHandler>,
+    layout: &MemoryLayout,
+    kinds: &[DeviceKind],
+) -> Result<Peripherals, String> {
+    configure_devices(memory, layout, kinds, vec![])
+}
+
+// Mounts the built in devices in kinds, then any extra devices. Err if two of them overlap.
+// A kind listed more than once is only mounted the first time.
+pub fn configure_devices(
+    memory: &mut SectionMemory<MmioHandler>,
+    layout: &MemoryLayout,
+    kinds: &[DeviceKind],
+    extra: Vec<Box<dyn MmioDevice>>,
+) -> Result<Peripherals, String> {
+    let peripherals = Peripherals::new();
+
+    {
+        let mut devices = peripherals.devices.lock().unwrap();
+
+        let mut mounted: Vec<DeviceKind> = vec![];
+
+        for &kind in kinds {
+            if mounted.contains(&kind) {
+                continue;
+            }
+
+            mounted.push(kind);
+            devices.register(kind.create(&peripherals))?;
+        }
+
+        for device in extra {
+            devices.register(device)?;
+        }
+
+        // Devices sharing state with the host, like the UART port, may still hold the last run's.
+        devices.reset();
+    }
 
-    memory.mount_listen(KEYBOARD_SELECTOR as usize, handler);
+    // Every device lives in the same 0xFFFF selector as the keyboard.
+    memory.mount_listen(
+        KEYBOARD_SELECTOR as usize,
+        MmioHandler::new(peripherals.devices.clone()),
+    );
 
     // Mark heap as "Writable"
-    for selector in 0x1000..0x8000 {
+    for selector in layout.writable_selectors() {
         memory.mount_writable(selector, 0xCC);
     }
 
-    keyboard
+    Ok(peripherals)
 }
 
 pub fn assemble(text: &str, path: Option<&str>) -> AssemblerResult {
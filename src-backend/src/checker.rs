@@ -0,0 +1,186 @@
+use crate::convention::{Convention, Violation};
+use crate::effects::{register_name, Effects};
+use crate::layout::MemoryLayout;
+use crate::shadow::{Shadow, ShadowRead, ShadowRegisters};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use titan::cpu::{Memory, State};
+use titan::unit::instruction::InstructionDecoder;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckMode {
+    #[default]
+    Off,
+    Warn,  // printed to the console, the program keeps going
+    Pause, // stops before the instruction runs
+}
+
+// Runtime checks, anything but Off runs the program one instruction at a time.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckOptions {
+    pub uninitialized: CheckMode,
+    pub convention: CheckMode,
+}
+
+pub struct Finding {
+    pub mode: CheckMode,
+    pub message: String,
+}
+
+// Taken before a step, commit compares it with the state after.
+pub struct Inspection {
+    pc: u32,
+    effects: Effects,
+    registers: ShadowRegisters,
+}
+
+pub struct Checker {
+    options: CheckOptions,
+    lines: HashMap<u32, usize>, // pc to source line, empty for ELF files
+    shadow: Shadow,
+    convention: Convention,
+}
+
+impl Checker {
+    // None if every check is off.
+    pub fn new(
+        options: CheckOptions,
+        layout: &MemoryLayout,
+        lines: HashMap<u32, usize>,
+    ) -> Option<Checker> {
+        if options.uninitialized == CheckMode::Off && options.convention == CheckMode::Off {
+            return None;
+        }
+
+        Some(Checker {
+            options,
+            lines,
+            shadow: Shadow::new(layout),
+            convention: Convention::new(),
+        })
+    }
+
+    // Names the instruction for messages, e.g. "lw at 0x00400010 (line 12)".
+    fn location<Mem: Memory>(&self, pc: u32, memory: &Mem) -> String {
+        let name = memory
+            .get_u32(pc)
+            .ok()
+            .and_then(|instruction| InstructionDecoder::decode(pc, instruction))
+            .map(|instruction| instruction.name())
+            .unwrap_or("instruction");
+
+        match self.lines.get(&pc) {
+            Some(line) => format!("{} at 0x{:08x} (line {})", name, pc, line + 1),
+            None => format!("{} at 0x{:08x}", name, pc),
+        }
+    }
+
+    pub fn inspect<Mem: Memory>(
+        &mut self,
+        state: &State<Mem>,
+    ) -> Option<(Inspection, Vec<Finding>)> {
+        let pc = state.registers.pc;
+        let instruction = state.memory.get_u32(pc).ok()?;
+
+        let effects = Effects::decode(instruction, &state.registers);
+        let registers = ShadowRegisters::from(&state.registers);
+
+        let mut findings = vec![];
+
+        if self.options.uninitialized != CheckMode::Off {
+            let reads = self.shadow.check(&effects, &registers);
+
+            if !reads.is_empty() {
+                let location = self.location(pc, &state.memory);
+
+                findings.extend(reads.into_iter().map(|read| Finding {
+                    mode: self.options.uninitialized,
+                    message: match read {
+                        ShadowRead::Register(register) => format!(
+                            "Uninitialized read: {} reads {} before anything was written to it.",
+                            location,
+                            register_name(register)
+                        ),
+                        ShadowRead::Memory { address, .. } => format!(
+                            "Uninitialized read: {} reads 0x{:08x}, which was never written.",
+                            location, address
+                        ),
+                    },
+                }))
+            }
+        }
+
+        if self.options.convention != CheckMode::Off {
+            let violations = self.convention.check(&effects, &registers);
+
+            findings.extend(violations.into_iter().map(|violation| Finding {
+                mode: self.options.convention,
+                message: self.violation(violation, pc, &state.memory),
+            }))
+        }
+
+        Some((
+            Inspection {
+                pc,
+                effects,
+                registers,
+            },
+            findings,
+        ))
+    }
+
+    fn violation<Mem: Memory>(&self, violation: Violation, pc: u32, memory: &Mem) -> String {
+        let location = self.location(pc, memory);
+
+        match violation {
+            Violation::Changed {
+                register,
+                before,
+                after,
+                call_pc,
+            } => format!(
+                "Calling convention: {} is not preserved by the call at {}. \
+                It was 0x{:08x} and is 0x{:08x} at the return, {}.",
+                register_name(register),
+                self.location(call_pc, memory),
+                before,
+                after,
+                location
+            ),
+            Violation::Temporary { register, call_pc } => format!(
+                "Calling convention: {} reads {} after the call at {} without writing it first, \
+                temporaries are not preserved across calls.",
+                location,
+                register_name(register),
+                self.location(call_pc, memory)
+            ),
+            Violation::ReturnAddress { call_pc } => format!(
+                "Calling convention: {} overwrites $ra before it was saved, \
+                the function called at {} can no longer return.",
+                location,
+                self.location(call_pc, memory)
+            ),
+        }
+    }
+
+    pub fn commit<Mem: Memory>(&mut self, inspection: &Inspection, state: &State<Mem>) {
+        let after = ShadowRegisters::from(&state.registers);
+
+        if self.options.uninitialized != CheckMode::Off {
+            self.shadow
+                .commit(&inspection.effects, &inspection.registers, &after)
+        }
+
+        if self.options.convention != CheckMode::Off {
+            self.convention.commit(
+                inspection.pc,
+                &inspection.effects,
+                &inspection.registers,
+                &after,
+                state.registers.pc,
+            )
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::effects::Effects;
+use crate::shadow::ShadowRegisters;
+use std::collections::VecDeque;
+
+// $s0 to $s7, $gp, $sp and $fp, a function has to hand these back unchanged.
+const PRESERVED: [u32; 11] = [16, 17, 18, 19, 20, 21, 22, 23, 28, 29, 30];
+
+// $t0 to $t9, a function is free to overwrite these.
+const TEMPORARY: u64 = (0xFF << 8) | (0b11 << 24);
+
+const RA: u32 = 31;
+
+// Frames past this are dropped from the bottom, runaway recursion is the stack guard's job.
+const MAX_FRAMES: usize = 0x10000;
+
+struct CallFrame {
+    call_pc: u32,
+    return_address: u32,
+    preserved: [u32; 11], // values of PRESERVED at the call
+    ra_saved: bool,       // the callee stored or copied $ra
+    returned: bool,       // the return was already checked
+    // The caller's stale temporaries, back in effect once the call returns.
+    clobbered: u64,
+    clobbered_by: u32,
+}
+
+pub enum Violation {
+    // A preserved register changed between the call and the return at pc.
+    Changed {
+        register: u32,
+        before: u32,
+        after: u32,
+        call_pc: u32,
+    },
+    // A temporary was read after a call without being written first.
+    Temporary {
+        register: u32,
+        call_pc: u32,
+    },
+    // A call overwrote $ra before the function entered at call_pc saved it.
+    ReturnAddress {
+        call_pc: u32,
+    },
+}
+
+// Follows jal and the matching jr to check the caller's registers survive the call.
+#[derive(Default)]
+pub struct Convention {
+    frames: VecDeque<CallFrame>,
+    // Temporaries the current function has not written since a call returned.
+    clobbered: u64,
+    clobbered_by: u32, // the call that returned
+}
+
+fn bits(mask: u64) -> impl Iterator<Item = u32> {
+    (0..64).filter(move |bit| mask & (1 << bit) != 0)
+}
+
+impl Convention {
+    pub fn new() -> Convention {
+        Convention::default()
+    }
+
+    // Runs before the instruction, so pausing leaves pc on the offending line.
+    pub fn check(&mut self, effects: &Effects, registers: &ShadowRegisters) -> Vec<Violation> {
+        let mut violations = vec![];
+
+        let used = effects.reads
+            | effects.stored.map_or(0, |register| 1 << register)
+            | effects.copy.map_or(0, |(_, source)| 1 << source);
+        let stale = used & self.clobbered;
+
+        // Reported once, reading it again is the same mistake.
+        self.clobbered &= !stale;
+
+        violations.extend(bits(stale).map(|register| Violation::Temporary {
+            register,
+            call_pc: self.clobbered_by,
+        }));
+
+        if effects.link == Some(RA) {
+            if let Some(frame) = self.frames.back_mut().filter(|frame| !frame.ra_saved) {
+                frame.ra_saved = true;
+
+                violations.push(Violation::ReturnAddress {
+                    call_pc: frame.call_pc,
+                })
+            }
+        }
+
+        if let Some(register) = effects.jump {
+            let target = registers.line[register as usize];
+
+            let frame = self
+                .frames
+                .iter_mut()
+                .rev()
+                .find(|frame| frame.return_address == target && !frame.returned);
+
+            if let Some(frame) = frame {
+                frame.returned = true;
+
+                violations.extend(PRESERVED.iter().zip(frame.preserved).filter_map(
+                    |(register, before)| {
+                        let after = registers.line[*register as usize];
+
+                        (before != after).then_some(Violation::Changed {
+                            register: *register,
+                            before,
+                            after,
+                            call_pc: frame.call_pc,
+                        })
+                    },
+                ))
+            }
+        }
+
+        violations
+    }
+
+    pub fn commit(
+        &mut self,
+        pc: u32,
+        effects: &Effects,
+        before: &ShadowRegisters,
+        after: &ShadowRegisters,
+        after_pc: u32,
+    ) {
+        self.clobbered &= !effects.writes;
+
+        if effects.stored == Some(RA) || effects.copy.is_some_and(|(_, source)| source == RA) {
+            if let Some(frame) = self.frames.back_mut() {
+                frame.ra_saved = true
+            }
+        }
+
+        if let Some(link) = effects.link {
+            let return_address = after.line[link as usize];
+
+            // bltzal and bgezal link even when the branch is not taken.
+            if after_pc != return_address {
+                if self.frames.len() >= MAX_FRAMES {
+                    self.frames.pop_front();
+                }
+
+                self.frames.push_back(CallFrame {
+                    call_pc: pc,
+                    return_address,
+                    preserved: PRESERVED.map(|register| before.line[register as usize]),
+                    ra_saved: false,
+                    returned: false,
+                    clobbered: self.clobbered,
+                    clobbered_by: self.clobbered_by,
+                });
+
+                self.clobbered = 0;
+            }
+        }
+
+        if effects.jump.is_some() {
+            let index = self
+                .frames
+                .iter()
+                .rposition(|frame| frame.return_address == after_pc);
+
+            // Frames above it belong to functions that never returned normally.
+            if let Some(index) = index {
+                let frame = self.frames.drain(index..).next().unwrap();
+
+                self.clobbered = frame.clobbered | TEMPORARY;
+                self.clobbered_by = frame.call_pc;
+            }
+        }
+    }
+}
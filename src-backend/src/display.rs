@@ -1,17 +1,75 @@
 use crate::execution::ReadDisplayTarget;
+use crate::graphics::GraphicsState;
+use crate::mmio::Peripherals;
+use crate::recording::RecordingBody;
+use crate::screen::ScreenState;
 use num::FromPrimitive;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use titan::cpu::{Memory, State};
 use titan::unit::register::RegisterName;
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum PixelFormat {
+    // 0x00RRGGBB words, alpha is always 255
+    #[default]
+    Rgb888,
+    // 0xAARRGGBB words
+    Argb8888,
+    // RRRRRGGGGGGBBBBB halfwords
+    Rgb565,
+    // Byte indices into 256 0x00RRGGBB words at palette
+    Palette8 {
+        palette: u32,
+    },
+    // Byte intensity
+    Grayscale8,
+    // One bit per pixel, most significant bit first, rows padded to a byte
+    Monochrome1,
+}
+
+impl PixelFormat {
+    // Used by the display protocol headers.
+    pub fn from_name(name: &str, palette: u32) -> Option<PixelFormat> {
+        Some(match name {
+            "rgb888" => PixelFormat::Rgb888,
+            "argb8888" => PixelFormat::Argb8888,
+            "rgb565" => PixelFormat::Rgb565,
+            "palette8" => PixelFormat::Palette8 { palette },
+            "grayscale8" => PixelFormat::Grayscale8,
+            "monochrome1" => PixelFormat::Monochrome1,
+            _ => return None,
+        })
+    }
+
+    // Used by the screen control registers.
+    pub fn from_code(code: u32, palette: u32) -> Option<PixelFormat> {
+        Some(match code {
+            0 => PixelFormat::Rgb888,
+            1 => PixelFormat::Argb8888,
+            2 => PixelFormat::Rgb565,
+            3 => PixelFormat::Palette8 { palette },
+            4 => PixelFormat::Grayscale8,
+            5 => PixelFormat::Monochrome1,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Clone, Serialize)]
 pub struct FlushDisplayState {
     pub address: u32,
     pub register: Option<u8>,
     pub width: u32,
     pub height: u32,
+    pub format: PixelFormat,
+    pub scale: Option<u32>, // host pixels per display pixel, if the program asked for one
     pub data: Option<Vec<u8>>, // flush should impact this
+    #[serde(skip)]
+    pub recording: Option<RecordingBody>, // every flush appends a frame while set
 }
 
 impl Default for FlushDisplayState {
@@ -21,7 +79,10 @@ impl Default for FlushDisplayState {
             register: None,
             width: 64,
             height: 64,
+            format: PixelFormat::default(),
+            scale: None,
             data: None,
+            recording: None,
         }
     }
 }
@@ -38,37 +99,199 @@ impl FlushDisplayState {
         }
     }
 
-    pub fn flush<Mem: Memory>(&mut self, state: &mut State<Mem>) {
+    fn read_memory<Mem: Memory>(&mut self, state: &mut State<Mem>) {
         let address = self.get_target().to_address(&state.registers);
 
-        self.data = read_display(address, self.width, self.height, &mut state.memory);
+        self.data = read_display(
+            address,
+            self.width,
+            self.height,
+            self.format,
+            &mut state.memory,
+        );
+    }
+
+    // Devices the program configured win over the host configuration.
+    // Without peripherals (displays other than MAIN_DISPLAY) memory is always read directly.
+    pub fn flush<Mem: Memory>(
+        &mut self,
+        state: &mut State<Mem>,
+        peripherals: Option<&Peripherals>,
+        now: Option<Duration>,
+    ) {
+        if let Some(peripherals) = peripherals {
+            let screen = peripherals.get::<ScreenState>();
+            let screen = screen.lock().unwrap();
+            let graphics = peripherals.get::<GraphicsState>();
+            let graphics = graphics.lock().unwrap();
+
+            if graphics.flush(self) {
+                // Composited by the graphics device.
+            } else if screen.configure(self) {
+                let frame = screen.read_display(&mut state.memory);
+
+                // A presented frame keeps the size it was captured with.
+                if let Some(frame) = &frame {
+                    self.width = frame.width;
+                    self.height = frame.height;
+                    self.scale = frame.scale;
+                }
+
+                self.data = frame.map(|frame| frame.data)
+            } else {
+                self.read_memory(state)
+            }
+        } else {
+            self.read_memory(state)
+        }
+
+        if let (Some(recording), Some(data)) = (&self.recording, &self.data) {
+            recording
+                .lock()
+                .unwrap()
+                .push(self.width, self.height, data, now)
+        }
+    }
+
+    // Recordings need frames while the program runs, not just when it stops.
+    pub fn recording_due(&self, now: Option<Duration>) -> bool {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.lock().unwrap().due(now))
+            .unwrap_or(false)
     }
 }
 
 pub type FlushDisplayBody = Arc<Mutex<FlushDisplayState>>;
 
+// The display that the screen and graphics devices draw to.
+pub const MAIN_DISPLAY: &str = "main";
+
+// Displays by id, every one of them is flushed on resume.
+pub type FlushDisplays = HashMap<String, FlushDisplayBody>;
+pub type FlushDisplaysBody = Arc<Mutex<FlushDisplays>>;
+
+pub fn single_display(display: FlushDisplayBody) -> FlushDisplays {
+    HashMap::from([(MAIN_DISPLAY.to_string(), display)])
+}
+
+// Peripherals to flush a display with, only the main display is handed to the program's devices.
+pub fn display_peripherals<'a>(id: &str, peripherals: &'a Peripherals) -> Option<&'a Peripherals> {
+    if id == MAIN_DISPLAY {
+        Some(peripherals)
+    } else {
+        None
+    }
+}
+
+fn rgb(pixel: u32, alpha: u8) -> [u8; 4] {
+    [
+        (pixel.wrapping_shr(16) & 0xFF) as u8,
+        (pixel.wrapping_shr(8) & 0xFF) as u8,
+        (pixel.wrapping_shr(0) & 0xFF) as u8,
+        alpha,
+    ]
+}
+
+fn rgb565(pixel: u16) -> [u8; 4] {
+    let red = ((pixel >> 11) & 0x1F) as u8;
+    let green = ((pixel >> 5) & 0x3F) as u8;
+    let blue = (pixel & 0x1F) as u8;
+
+    // Replicate the high bits so 0x1F maps to 0xFF.
+    [
+        (red << 3) | (red >> 2),
+        (green << 2) | (green >> 4),
+        (blue << 3) | (blue >> 2),
+        255,
+    ]
+}
+
+fn get_u16<Mem: Memory + ?Sized>(memory: &Mem, address: u32) -> Option<u16> {
+    let low = memory.get(address).ok()? as u16;
+    let high = memory.get(address.wrapping_add(1)).ok()? as u16;
+
+    Some((high << 8) | low)
+}
+
+// What a display shows, read in one go so the size always matches the data.
+#[derive(Clone)]
+pub struct DisplayFrame {
+    pub width: u32,
+    pub height: u32,
+    pub scale: Option<u32>, // host pixels per display pixel, if the program asked for one
+    pub data: Vec<u8>,      // RGBA
+}
+
 // NOT a tauri command.
-pub fn read_display<Mem: Memory>(
+pub fn read_display<Mem: Memory + ?Sized>(
     address: u32,
     width: u32,
     height: u32,
+    format: PixelFormat,
     memory: &mut Mem,
 ) -> Option<Vec<u8>> {
     let pixels = width.checked_mul(height)?;
 
     let mut result = vec![0u8; (pixels * 4) as usize];
 
+    let palette = match format {
+        PixelFormat::Palette8 { palette } => (0..256u32)
+            .map(|i| memory.get_u32(palette.wrapping_add(i.wrapping_mul(4))).ok())
+            .collect::<Option<Vec<u32>>>()?,
+        _ => vec![],
+    };
+
+    // Rows of monochrome pixels start on a byte boundary.
+    let stride = width.div_ceil(8);
+
     for i in 0..pixels {
-        let point = address.wrapping_add(i.wrapping_mul(4));
+        let color = match format {
+            // Assuming little endian: 0xAARRGGBB -> [BB, GG, RR, AA] -> want [RR, GG, BB, AA]
+            PixelFormat::Rgb888 => {
+                let pixel = memory
+                    .get_u32(address.wrapping_add(i.wrapping_mul(4)))
+                    .ok()?;
+
+                rgb(pixel, 255)
+            }
+            PixelFormat::Argb8888 => {
+                let pixel = memory
+                    .get_u32(address.wrapping_add(i.wrapping_mul(4)))
+                    .ok()?;
+
+                rgb(pixel, (pixel.wrapping_shr(24) & 0xFF) as u8)
+            }
+            PixelFormat::Rgb565 => {
+                rgb565(get_u16(memory, address.wrapping_add(i.wrapping_mul(2)))?)
+            }
+            PixelFormat::Palette8 { .. } => {
+                let index = memory.get(address.wrapping_add(i)).ok()?;
+
+                rgb(palette[index as usize], 255)
+            }
+            PixelFormat::Grayscale8 => {
+                let value = memory.get(address.wrapping_add(i)).ok()?;
+
+                [value, value, value, 255]
+            }
+            PixelFormat::Monochrome1 => {
+                let (x, y) = (i % width, i / width);
+                let byte = memory
+                    .get(address.wrapping_add(y.wrapping_mul(stride).wrapping_add(x / 8)))
+                    .ok()?;
+                let value = if byte & (0x80 >> (x % 8)) != 0 {
+                    255
+                } else {
+                    0
+                };
 
-        let pixel = memory.get_u32(point).ok()?;
+                [value, value, value, 255]
+            }
+        };
 
-        // Assuming little endian: 0xAARRGGBB -> [BB, GG, RR, AA] -> want [RR, GG, BB, AA]
         let start = (i as usize) * 4;
-        result[start] = (pixel.wrapping_shr(16) & 0xFF) as u8;
-        result[start + 1] = (pixel.wrapping_shr(8) & 0xFF) as u8;
-        result[start + 2] = (pixel.wrapping_shr(0) & 0xFF) as u8;
-        result[start + 3] = 255;
+        result[start..start + 4].copy_from_slice(&color);
     }
 
     Some(result)
@@ -0,0 +1,215 @@
+use titan::cpu::state::Registers;
+
+// Shadow indices past the 32 line registers.
+pub const HI_SHADOW: u32 = 32;
+pub const LO_SHADOW: u32 = 33;
+
+const V0: u32 = 2;
+const A0: u32 = 4;
+const A1: u32 = 5;
+const A2: u32 = 6;
+const A3: u32 = 7;
+const RA: u32 = 31;
+
+const REGISTER_NAMES: [&str; 34] = [
+    "$zero", "$at", "$v0", "$v1", "$a0", "$a1", "$a2", "$a3", "$t0", "$t1", "$t2", "$t3", "$t4",
+    "$t5", "$t6", "$t7", "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7", "$t8", "$t9",
+    "$k0", "$k1", "$gp", "$sp", "$fp", "$ra", "hi", "lo",
+];
+
+pub fn register_name(register: u32) -> &'static str {
+    REGISTER_NAMES
+        .get(register as usize)
+        .copied()
+        .unwrap_or("$?")
+}
+
+fn bit(register: u32) -> u64 {
+    1 << register
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MemoryAccess {
+    pub address: u32,
+    pub size: u32,
+    pub store: bool,
+}
+
+// What an instruction reads and writes, decoded straight from the word.
+// Only integer registers (and HI/LO) are covered, floating point values are not tracked.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Effects {
+    pub reads: u64,  // one bit per register, HI_SHADOW and LO_SHADOW included
+    pub writes: u64, // same layout as reads
+    pub access: Option<MemoryAccess>,
+    pub stored: Option<u32>,      // register a store copies into memory
+    pub loaded: Option<u32>,      // register a load fills from memory
+    pub copy: Option<(u32, u32)>, // move: destination, source
+    pub syscall: Option<u32>,     // the code in $v0
+    pub link: Option<u32>,        // register a call puts the return address in
+    pub jump: Option<u32>,        // register jr jumps to
+}
+
+// Registers each syscall takes its arguments from, $v0 is always read.
+fn syscall_arguments(code: u32) -> u64 {
+    let a = |registers: &[u32]| registers.iter().fold(0, |mask, r| mask | bit(*r));
+
+    match code {
+        1 | 4 | 9 | 11 | 16 | 17 | 32 | 34 | 35 | 36 | 41 => a(&[A0]),
+        8 | 40 | 42 => a(&[A0, A1]),
+        13 | 14 | 15 => a(&[A0, A1, A2]),
+        31 | 33 => a(&[A0, A1, A2, A3]),
+        _ => 0,
+    }
+}
+
+// Branches and jumps, the instruction after one is not necessarily the next word.
+pub fn transfers(instruction: u32) -> bool {
+    let opcode = instruction >> 26;
+    let rs = (instruction >> 21) & 0x1F;
+    let funct = instruction & 0x3F;
+
+    match opcode {
+        0x00 => funct == 0x08 || funct == 0x09,
+        0x01..=0x07 | 0x14..=0x17 => true,
+        0x11 => rs == 0x08, // bc1f, bc1t
+        _ => false,
+    }
+}
+
+impl Effects {
+    fn uses(&mut self, reads: &[u32], writes: &[u32]) {
+        for register in reads {
+            self.reads |= bit(*register)
+        }
+
+        for register in writes {
+            self.writes |= bit(*register)
+        }
+    }
+
+    pub fn decode(instruction: u32, registers: &Registers) -> Effects {
+        let opcode = instruction >> 26;
+        let rs = (instruction >> 21) & 0x1F;
+        let rt = (instruction >> 16) & 0x1F;
+        let rd = (instruction >> 11) & 0x1F;
+        let funct = instruction & 0x3F;
+
+        let address = registers.line[rs as usize].wrapping_add(instruction as i16 as u32);
+
+        let mut effects = Effects::default();
+
+        let load = |effects: &mut Effects, size: u32| {
+            effects.uses(&[rs], &[rt]);
+            effects.loaded = Some(rt);
+            effects.access = Some(MemoryAccess {
+                address,
+                size,
+                store: false,
+            })
+        };
+
+        let store = |effects: &mut Effects, size: u32| {
+            effects.uses(&[rs], &[]);
+            effects.stored = Some(rt);
+            effects.access = Some(MemoryAccess {
+                address,
+                size,
+                store: true,
+            })
+        };
+
+        match opcode {
+            0x00 => match funct {
+                0x00 | 0x02 | 0x03 => effects.uses(&[rt], &[rd]),
+                0x04 | 0x06 | 0x07 => effects.uses(&[rs, rt], &[rd]),
+                0x08 => {
+                    effects.uses(&[rs], &[]);
+                    effects.jump = Some(rs)
+                }
+                0x09 => {
+                    effects.uses(&[rs], &[rd]);
+                    effects.link = Some(rd)
+                }
+                0x0A | 0x0B => effects.uses(&[rs, rt], &[rd]),
+                0x0C => {
+                    let code = registers.line[V0 as usize];
+
+                    effects.reads = bit(V0) | syscall_arguments(code);
+                    effects.syscall = Some(code)
+                }
+                0x10 => effects.uses(&[HI_SHADOW], &[rd]),
+                0x11 => effects.uses(&[rs], &[HI_SHADOW]),
+                0x12 => effects.uses(&[LO_SHADOW], &[rd]),
+                0x13 => effects.uses(&[rs], &[LO_SHADOW]),
+                0x18..=0x1B => effects.uses(&[rs, rt], &[HI_SHADOW, LO_SHADOW]),
+                // move is addu or or with $zero, it copies definedness instead of reading.
+                0x21 | 0x25 if rs == 0 || rt == 0 => {
+                    effects.writes = bit(rd);
+                    effects.copy = Some((rd, rs.max(rt)))
+                }
+                0x20..=0x27 | 0x2A | 0x2B => effects.uses(&[rs, rt], &[rd]),
+                0x30..=0x36 => effects.uses(&[rs, rt], &[]),
+                _ => {}
+            },
+            // bltzal, bgezal
+            0x01 if rt & 0x10 != 0 => {
+                effects.uses(&[rs], &[RA]);
+                effects.link = Some(RA)
+            }
+            0x01 => effects.uses(&[rs], &[]),
+            0x03 => {
+                effects.uses(&[], &[RA]);
+                effects.link = Some(RA)
+            }
+            0x04 | 0x05 => effects.uses(&[rs, rt], &[]),
+            0x06 | 0x07 => effects.uses(&[rs], &[]),
+            0x08..=0x0E => effects.uses(&[rs], &[rt]),
+            0x0F => effects.uses(&[], &[rt]),
+            // Coprocessor moves, mfc/cfc write rt and mtc/ctc read it.
+            0x10 | 0x11 => match rs {
+                0x00 | 0x02 => effects.uses(&[], &[rt]),
+                0x04 | 0x06 => effects.uses(&[rt], &[]),
+                _ => {}
+            },
+            0x1C => match funct {
+                0x02 => effects.uses(&[rs, rt], &[rd]),
+                0x00 | 0x01 | 0x04 | 0x05 => {
+                    effects.uses(&[rs, rt, HI_SHADOW, LO_SHADOW], &[HI_SHADOW, LO_SHADOW])
+                }
+                0x20 | 0x21 => effects.uses(&[rs], &[rd]),
+                _ => {}
+            },
+            0x20 | 0x24 => load(&mut effects, 1),
+            0x21 | 0x25 => load(&mut effects, 2),
+            0x23 | 0x30 => load(&mut effects, 4),
+            // lwl and lwr merge into rt and touch a partial word, only registers are checked.
+            0x22 | 0x26 => effects.uses(&[rs, rt], &[rt]),
+            0x28 => store(&mut effects, 1),
+            0x29 => store(&mut effects, 2),
+            0x2B => store(&mut effects, 4),
+            0x2A | 0x2E => effects.uses(&[rs, rt], &[]),
+            0x38 => {
+                store(&mut effects, 4);
+                effects.writes |= bit(rt)
+            }
+            // Floating point loads and stores, the value side is not tracked.
+            0x31 | 0x35 => {
+                load(&mut effects, if opcode == 0x31 { 4 } else { 8 });
+                effects.writes = 0;
+                effects.loaded = None
+            }
+            0x39 | 0x3D => {
+                store(&mut effects, if opcode == 0x39 { 4 } else { 8 });
+                effects.stored = None
+            }
+            _ => {}
+        }
+
+        // $zero is always defined, writes to it go nowhere.
+        effects.reads &= !1;
+        effects.writes &= !1;
+
+        effects
+    }
+}
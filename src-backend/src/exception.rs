@@ -0,0 +1,303 @@
+use titan::cpu::error::Error;
+use titan::cpu::error::Error::{CpuTrap, MemoryAlign, MemoryUnmapped};
+use titan::cpu::{Memory, State};
+
+// Same location as MARS, user handlers are placed here with .ktext
+pub const EXCEPTION_HANDLER: u32 = 0x80000180;
+
+// Coprocessor 0 register numbers, as used by mfc0/mtc0.
+pub const BAD_VADDR_REGISTER: u32 = 8;
+pub const STATUS_REGISTER: u32 = 12;
+pub const CAUSE_REGISTER: u32 = 13;
+pub const EPC_REGISTER: u32 = 14;
+
+pub const STATUS_INTERRUPT_ENABLE: u32 = 1 << 0;
+pub const STATUS_EXCEPTION_LEVEL: u32 = 1 << 1;
+
+const CAUSE_CODE_MASK: u32 = 0x1F << 2;
+const INTERRUPT_MASK: u32 = 0xFF << 8; // IM bits in Status, IP bits in Cause
+
+const ERET_INSTRUCTION: u32 = 0x42000018;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExceptionCode {
+    Interrupt = 0,
+    AddressLoad = 4,
+    AddressStore = 5,
+    Overflow = 12,
+    Trap = 13,
+}
+
+#[derive(Clone, Debug)]
+pub struct Coprocessor0 {
+    pub bad_vaddr: u32,
+    pub status: u32,
+    pub cause: u32,
+    pub epc: u32,
+}
+
+impl Default for Coprocessor0 {
+    fn default() -> Self {
+        Coprocessor0 {
+            bad_vaddr: 0,
+            status: 0x0000FF11, // MARS default, all interrupts unmasked and enabled
+            cause: 0,
+            epc: 0,
+        }
+    }
+}
+
+impl Coprocessor0 {
+    pub fn get(&self, register: u32) -> u32 {
+        match register {
+            BAD_VADDR_REGISTER => self.bad_vaddr,
+            STATUS_REGISTER => self.status,
+            CAUSE_REGISTER => self.cause,
+            EPC_REGISTER => self.epc,
+            _ => 0,
+        }
+    }
+
+    pub fn set(&mut self, register: u32, value: u32) {
+        match register {
+            BAD_VADDR_REGISTER => self.bad_vaddr = value,
+            STATUS_REGISTER => self.status = value,
+            CAUSE_REGISTER => self.cause = value,
+            EPC_REGISTER => self.epc = value,
+            _ => {}
+        }
+    }
+
+    pub fn in_handler(&self) -> bool {
+        self.status & STATUS_EXCEPTION_LEVEL != 0
+    }
+
+    pub fn raise(&mut self, code: ExceptionCode, epc: u32) {
+        self.cause = (self.cause & !CAUSE_CODE_MASK) | ((code as u32) << 2);
+        self.status |= STATUS_EXCEPTION_LEVEL;
+        self.epc = epc;
+    }
+}
+
+enum Cop0Instruction {
+    MoveFrom { target: usize, register: u32 },
+    MoveTo { source: usize, register: u32 },
+    Return,
+}
+
+fn decode_cop0(instruction: u32) -> Option<Cop0Instruction> {
+    if instruction == ERET_INSTRUCTION {
+        return Some(Cop0Instruction::Return);
+    }
+
+    let opcode = instruction >> 26;
+    let format = (instruction >> 21) & 0x1F;
+    let line = ((instruction >> 16) & 0x1F) as usize;
+    let register = (instruction >> 11) & 0x1F;
+
+    // Lower 11 bits are always zero for the moves.
+    if opcode != 0x10 || instruction & 0x7FF != 0 {
+        return None;
+    }
+
+    match format {
+        0 => Some(Cop0Instruction::MoveFrom {
+            target: line,
+            register,
+        }),
+        4 => Some(Cop0Instruction::MoveTo {
+            source: line,
+            register,
+        }),
+        _ => None,
+    }
+}
+
+fn is_store(instruction: u32) -> bool {
+    // sb, sh, swl, sw, swr, swc1, sdc1
+    matches!(
+        instruction >> 26,
+        0x28 | 0x29 | 0x2A | 0x2B | 0x2E | 0x39 | 0x3D
+    )
+}
+
+fn is_trap(instruction: u32) -> bool {
+    let opcode = instruction >> 26;
+
+    match opcode {
+        0 => (0x30..=0x36).contains(&(instruction & 0x3F)),
+        1 => (0x08..=0x0E).contains(&((instruction >> 16) & 0x1F)),
+        _ => false,
+    }
+}
+
+fn effective_address<Mem: Memory>(instruction: u32, state: &State<Mem>) -> u32 {
+    let base = state.registers.line[((instruction >> 21) & 0x1F) as usize];
+    let offset = instruction as u16 as i16 as i32 as u32;
+
+    base.wrapping_add(offset)
+}
+
+// Traps are reported after the pc has moved past the instruction.
+pub fn fault_pc(error: &Error, pc: u32) -> u32 {
+    match error {
+        CpuTrap => pc.wrapping_sub(4),
+        _ => pc,
+    }
+}
+
+fn emulate_cop0<Mem: Memory>(cop0: &mut Coprocessor0, pc: u32, state: &mut State<Mem>) -> bool {
+    let Some(instruction) = state.memory.get_u32(pc).ok().and_then(decode_cop0) else {
+        return false;
+    };
+
+    match instruction {
+        Cop0Instruction::MoveFrom { target, register } => {
+            if target != 0 {
+                state.registers.line[target] = cop0.get(register);
+            }
+
+            state.registers.pc = pc.wrapping_add(4);
+        }
+        Cop0Instruction::MoveTo { source, register } => {
+            cop0.set(register, state.registers.line[source]);
+
+            state.registers.pc = pc.wrapping_add(4);
+        }
+        Cop0Instruction::Return => {
+            cop0.status &= !STATUS_EXCEPTION_LEVEL;
+
+            state.registers.pc = cop0.epc;
+        }
+    }
+
+    true
+}
+
+fn exception_for<Mem: Memory>(
+    error: &Error,
+    pc: u32,
+    state: &State<Mem>,
+) -> Option<(ExceptionCode, Option<u32>)> {
+    let instruction = state.memory.get_u32(pc).ok();
+
+    let address_code = |instruction: Option<u32>| {
+        if instruction.map(is_store).unwrap_or(false) {
+            ExceptionCode::AddressStore
+        } else {
+            ExceptionCode::AddressLoad
+        }
+    };
+
+    match error {
+        MemoryUnmapped(address) => Some((address_code(instruction), Some(*address))),
+        MemoryAlign(_, _) => {
+            // Fetching a misaligned pc has no instruction to blame.
+            let address = instruction
+                .map(|instruction| effective_address(instruction, state))
+                .unwrap_or(pc);
+
+            Some((address_code(instruction), Some(address)))
+        }
+        CpuTrap => {
+            let code = if instruction.map(is_trap).unwrap_or(false) {
+                ExceptionCode::Trap
+            } else {
+                ExceptionCode::Overflow
+            };
+
+            Some((code, None))
+        }
+        _ => None,
+    }
+}
+
+// Mounting any .ktext or .kdata maps the whole selector, so a zero word means nothing was placed here.
+pub fn handler_installed<Mem: Memory>(memory: &Mem) -> bool {
+    matches!(memory.get_u32(EXCEPTION_HANDLER), Ok(instruction) if instruction != 0)
+}
+
+// Moves to the user handler, false if there is none or it is the one that faulted.
+fn enter_handler<Mem: Memory>(
+    cop0: &mut Coprocessor0,
+    code: ExceptionCode,
+    pc: u32,
+    address: Option<u32>,
+    state: &mut State<Mem>,
+) -> bool {
+    // A fault inside the handler would just loop forever.
+    if cop0.in_handler() || !handler_installed(&state.memory) {
+        return false;
+    }
+
+    if let Some(address) = address {
+        cop0.bad_vaddr = address;
+    }
+
+    cop0.raise(code, pc);
+
+    state.registers.pc = EXCEPTION_HANDLER;
+
+    true
+}
+
+// Returns true if execution can continue, false if the error should be reported.
+pub fn handle_exception<Mem: Memory>(
+    cop0: &mut Coprocessor0,
+    error: &Error,
+    state: &mut State<Mem>,
+) -> bool {
+    let pc = fault_pc(error, state.registers.pc);
+
+    if emulate_cop0(cop0, pc, state) {
+        return true;
+    }
+
+    let Some((code, address)) = exception_for(error, pc, state) else {
+        return false;
+    };
+
+    enter_handler(cop0, code, pc, address, state)
+}
+
+// Errors a syscall ran into while reading or writing memory for the program.
+// The trap already moved pc past the syscall, EPC points back at it.
+pub fn handle_syscall_exception<Mem: Memory>(
+    cop0: &mut Coprocessor0,
+    error: &Error,
+    state: &mut State<Mem>,
+) -> bool {
+    let pc = state.registers.pc.wrapping_sub(4);
+
+    let (code, address) = match error {
+        MemoryUnmapped(address) => (ExceptionCode::AddressLoad, Some(*address)),
+        MemoryAlign(_, _) => (ExceptionCode::AddressLoad, None),
+        CpuTrap => (ExceptionCode::Overflow, None),
+        _ => return false,
+    };
+
+    enter_handler(cop0, code, pc, address, state)
+}
+
+// Takes the interrupt if one is pending and unmasked, returns true if the handler was entered.
+pub fn deliver_interrupts<Mem: Memory>(
+    cop0: &mut Coprocessor0,
+    pending: u32,
+    state: &mut State<Mem>,
+) -> bool {
+    cop0.cause = (cop0.cause & !INTERRUPT_MASK) | (pending & INTERRUPT_MASK);
+
+    let enabled = cop0.status & STATUS_INTERRUPT_ENABLE != 0;
+    let unmasked = cop0.cause & cop0.status & INTERRUPT_MASK != 0;
+
+    if !enabled || !unmasked || cop0.in_handler() || !handler_installed(&state.memory) {
+        return false;
+    }
+
+    // Interrupts happen between instructions, so eret resumes at the current pc.
+    cop0.raise(ExceptionCode::Interrupt, state.registers.pc);
+
+    state.registers.pc = EXCEPTION_HANDLER;
+
+    true
+}
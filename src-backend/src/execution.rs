@@ -1,9 +1,20 @@
+use crate::audio::{AudioCapture, AudioCaptureBody, AudioClip, AudioState};
 use crate::device::ExecutionState;
-use crate::display::{read_display, FlushDisplayBody};
+use crate::display::{display_peripherals, read_display, DisplayFrame, FlushDisplays, PixelFormat};
+use crate::graphics::{GraphicsState, GRAPHICS_HEIGHT, GRAPHICS_WIDTH};
+use crate::heap::HeapReport;
+use crate::keyboard::KeyboardState;
+use crate::lab::{LabDisplay, LabState};
+use crate::mouse::MouseState;
+use crate::registry::DeviceSnapshot;
+use crate::scancode::{key_code, ScanCodeState};
+use crate::screen::ScreenState;
 use crate::syscall::{SyscallDelegate, SyscallResult};
+use crate::text::{TextDisplay, TextState};
 use async_trait::async_trait;
 use serde::Serialize;
 use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use titan::cpu::error::Error::{CpuTrap, MemoryAlign, MemoryUnmapped};
 use titan::cpu::memory::section::{ListenResponder, SectionMemory};
 use titan::cpu::memory::watched::WatchedMemory;
@@ -24,6 +35,7 @@ pub enum ResumeMode {
     Paused,
     Breakpoint,
     Finished { pc: u32, code: Option<u32> },
+    Flagged { message: String }, // paused by a runtime check, can be resumed
 }
 
 fn format_error<Mem: Memory>(error: titan::cpu::error::Error, state: &State<Mem>) -> String {
@@ -80,12 +92,51 @@ impl ResumeMode {
     }
 }
 
+// Index layout for write_register, line registers take 0 to 31.
+pub const HI_REGISTER: u32 = 32;
+pub const LO_REGISTER: u32 = 33;
+pub const PC_REGISTER: u32 = 34;
+pub const FP_REGISTER_START: u32 = 35; // $f0 to $f31 take 35 to 66
+pub const FCC_REGISTER: u32 = FP_REGISTER_START + 32;
+
+#[derive(Serialize)]
+pub struct FloatingRegistersResult {
+    raw: [u32; 32],
+    single: [f32; 32],
+    // Even/odd pairs, double[n] is made from $f(2n) (low) and $f(2n + 1) (high).
+    double: [f64; 16],
+    // One bit per condition flag, bit n is FCC n.
+    condition: u8,
+}
+
+impl FloatingRegistersResult {
+    fn from_registers(registers: &Registers) -> FloatingRegistersResult {
+        let raw = registers.fp;
+
+        let single = raw.map(f32::from_bits);
+        let double = std::array::from_fn(|index| {
+            let low = raw[index * 2] as u64;
+            let high = raw[index * 2 + 1] as u64;
+
+            f64::from_bits((high << 32) | low)
+        });
+
+        FloatingRegistersResult {
+            raw,
+            single,
+            double,
+            condition: registers.fcc,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct RegistersResult {
     pc: u32,
     line: [u32; 32],
     lo: u32,
     hi: u32,
+    fp: FloatingRegistersResult,
 }
 
 impl From<Registers> for RegistersResult {
@@ -95,6 +146,7 @@ impl From<Registers> for RegistersResult {
             line: value.line,
             lo: value.lo,
             hi: value.hi,
+            fp: FloatingRegistersResult::from_registers(&value),
         }
     }
 }
@@ -119,6 +171,7 @@ impl ResumeResult {
                 code: Some(code),
             },
             Some(SyscallResult::Aborted) => ResumeMode::Paused,
+            Some(SyscallResult::Flagged(message)) => ResumeMode::Flagged { message },
             Some(SyscallResult::Exception(error)) => ResumeMode::Invalid {
                 message: format_error(error, state),
             },
@@ -182,7 +235,7 @@ pub struct BatchOptions {
 pub struct ResumeOptions {
     pub batch: Option<BatchOptions>,
     pub breakpoints: Option<Vec<u32>>,
-    pub display: Option<FlushDisplayBody>,
+    pub displays: FlushDisplays,
     // if set_running is true, set state to "Running" and clear cancellation
     // useful for looping batches, like in the WASM backend
     pub change_state: Option<ExecutorMode>,
@@ -212,13 +265,42 @@ pub trait ExecutionDevice: Send + Sync {
     fn set_breakpoints(&self, breakpoints: HashSet<u32>);
 
     fn read_bytes(&self, address: u32, count: u32) -> Option<Vec<Option<u8>>>;
-    fn read_display(&self, target: ReadDisplayTarget, width: u32, height: u32) -> Option<Vec<u8>>;
+    // Only the main display shows what the screen and graphics devices draw.
+    // The frame has the program's resolution when it configured the display itself.
+    fn read_display(
+        &self,
+        target: ReadDisplayTarget,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        main: bool,
+    ) -> Option<DisplayFrame>;
+    fn read_text(&self) -> TextDisplay;
+    fn read_lab(&self) -> LabDisplay;
+    // UI state of every registered device that has one.
+    fn read_devices(&self) -> Vec<DeviceSnapshot>;
+    // Every sbrk allocation with a summary of what it holds.
+    fn read_heap(&self) -> HeapReport;
+    // Oldest audio buffer the frontend has not played yet.
+    fn take_audio(&self) -> Option<AudioClip>;
+    fn start_audio_capture(&self);
+    // Stops the capture.
+    fn take_audio_capture(&self) -> Option<AudioCaptureBody>;
 
     fn write_bytes(&self, address: u32, bytes: Vec<u8>);
     fn write_register(&self, register: u32, value: u32);
 
     fn wake_sync(&self);
+    // Called by the frontend after it draws a frame.
+    fn wake_refresh(&self);
     fn post_key(&self, key: char, up: bool);
+    // Code is a KeyboardEvent.code name, like "ArrowUp".
+    fn post_key_code(&self, code: &str, up: bool);
+    // Position is in display pixels, buttons is a MOUSE_* bitmask.
+    fn post_mouse(&self, x: i32, y: i32, buttons: u32);
+    // Key is the keypad value, 0x0 to 0xF.
+    fn post_keypad(&self, key: u8, up: bool);
+    fn post_switch(&self, index: u8, on: bool);
     fn post_input(&self, text: String);
 }
 
@@ -255,7 +337,8 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice
             state.lock().unwrap().clear_cancelled();
         }
 
-        let delegate = SyscallDelegate::new(state);
+        let delegate =
+            SyscallDelegate::new(state, self.peripherals.clone(), options.displays.clone());
 
         let (frame, result) = {
             if let Some(batch) = &options.batch {
@@ -285,10 +368,14 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice
             }
         };
 
-        if let Some(display) = &options.display {
+        let now = self.delegate.lock().unwrap().now();
+
+        for (id, display) in &options.displays {
             let mut lock = display.lock().unwrap();
+            let peripherals = display_peripherals(id, &self.peripherals);
 
-            debugger_clone.with_state(|state| lock.flush(state))
+            // Devices are locked while the state is held, never the other way around.
+            debugger_clone.with_state(|state| lock.flush(state, peripherals, now))
         }
 
         debugger.with_state(|state| {
@@ -323,14 +410,96 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice
         Some(value)
     }
 
-    fn read_display(&self, target: ReadDisplayTarget, width: u32, height: u32) -> Option<Vec<u8>> {
+    fn read_display(
+        &self,
+        target: ReadDisplayTarget,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        main: bool,
+    ) -> Option<DisplayFrame> {
+        if main {
+            let graphics = self.peripherals.get::<GraphicsState>();
+            let graphics = graphics.lock().unwrap();
+
+            if graphics.enabled() {
+                return Some(DisplayFrame {
+                    width: GRAPHICS_WIDTH,
+                    height: GRAPHICS_HEIGHT,
+                    scale: None,
+                    data: graphics.render(),
+                });
+            }
+        }
+
         self.debugger.with_state(|state| {
+            let screen = self.peripherals.get::<ScreenState>();
+            let screen = screen.lock().unwrap();
+
+            // The program's own configuration wins over the host's.
+            if main && screen.enabled() {
+                return screen.read_display(&mut state.memory);
+            }
+
             let address = target.to_address(&state.registers);
+            let data = read_display(address, width, height, format, &mut state.memory)?;
+
+            Some(DisplayFrame {
+                width,
+                height,
+                scale: None,
+                data,
+            })
+        })
+    }
+
+    fn read_text(&self) -> TextDisplay {
+        self.peripherals
+            .with(|text: &mut TextState| text.snapshot())
+    }
+
+    fn read_lab(&self) -> LabDisplay {
+        self.peripherals.with(|lab: &mut LabState| lab.snapshot())
+    }
+
+    fn read_devices(&self) -> Vec<DeviceSnapshot> {
+        self.peripherals.devices.lock().unwrap().snapshots()
+    }
 
-            read_display(address, width, height, &mut state.memory)
+    fn read_heap(&self) -> HeapReport {
+        // Same order as the delegate's tick, syscall state first.
+        let mut delegate = self.delegate.lock().unwrap();
+
+        self.debugger.with_state(|state| {
+            let stack_pointer = state.registers.line[29];
+
+            delegate.sample_stack(stack_pointer);
+
+            delegate.heap.report(
+                stack_pointer,
+                delegate.stack_low_water,
+                delegate.layout.stack_pointer,
+                &state.memory,
+            )
         })
     }
 
+    fn take_audio(&self) -> Option<AudioClip> {
+        self.peripherals
+            .with(|audio: &mut AudioState| audio.take_clip())
+    }
+
+    fn start_audio_capture(&self) {
+        self.peripherals.with(|audio: &mut AudioState| {
+            audio.capture = Some(Arc::new(Mutex::new(AudioCapture::new())))
+        })
+    }
+
+    fn take_audio_capture(&self) -> Option<AudioCaptureBody> {
+        self.peripherals
+            .with(|audio: &mut AudioState| audio.capture.take())
+    }
+
     fn write_bytes(&self, address: u32, bytes: Vec<u8>) {
         self.debugger.with_memory(|memory| {
             for (index, byte) in bytes.iter().enumerate() {
@@ -342,9 +511,13 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice
     fn write_register(&self, register: u32, value: u32) {
         self.debugger.with_state(|state| match register {
             0..=31 => state.registers.line[register as usize] = value,
-            32 => state.registers.hi = value,
-            33 => state.registers.lo = value,
-            34 => state.registers.pc = value,
+            HI_REGISTER => state.registers.hi = value,
+            LO_REGISTER => state.registers.lo = value,
+            PC_REGISTER => state.registers.pc = value,
+            FP_REGISTER_START..FCC_REGISTER => {
+                state.registers.fp[(register - FP_REGISTER_START) as usize] = value
+            }
+            FCC_REGISTER => state.registers.fcc = value as u8,
             _ => {}
         })
     }
@@ -355,8 +528,40 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice
         }
     }
 
+    fn wake_refresh(&self) {
+        if let Some(sender) = self.delegate.lock().unwrap().refresh_wake.take() {
+            sender.send(()).ok();
+        }
+    }
+
     fn post_key(&self, key: char, up: bool) {
-        self.keyboard.lock().unwrap().push_key(key, up)
+        self.peripherals
+            .with(|keyboard: &mut KeyboardState| keyboard.push_key(key, up))
+    }
+
+    fn post_key_code(&self, code: &str, up: bool) {
+        let Some(code) = key_code(code) else { return };
+
+        let time = self.delegate.lock().unwrap().now();
+        let millis = time.map(|time| time.as_millis() as u64).unwrap_or(0);
+
+        self.peripherals
+            .with(|scancode: &mut ScanCodeState| scancode.push_key(code, up, millis))
+    }
+
+    fn post_mouse(&self, x: i32, y: i32, buttons: u32) {
+        self.peripherals
+            .with(|mouse: &mut MouseState| mouse.post_mouse(x, y, buttons))
+    }
+
+    fn post_keypad(&self, key: u8, up: bool) {
+        self.peripherals
+            .with(|lab: &mut LabState| lab.post_keypad(key, up))
+    }
+
+    fn post_switch(&self, index: u8, on: bool) {
+        self.peripherals
+            .with(|lab: &mut LabState| lab.post_switch(index, on))
     }
 
     fn post_input(&self, text: String) {
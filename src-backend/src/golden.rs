@@ -0,0 +1,396 @@
+use crate::audio;
+use crate::build::{configure_devices, get_binary_finished_pcs};
+use crate::device::{state_from_binary, ExecutionState};
+use crate::display::{single_display, FlushDisplayBody, FlushDisplays};
+use crate::execution::{ExecutionDevice, ResumeMode, ResumeOptions};
+use crate::layout::MemoryLayout;
+use crate::mmio::MmioHandler;
+use crate::recording::{Recording, RecordingFormat};
+use crate::registry::DeviceKind;
+use crate::syscall::{ConsoleHandler, MidiHandler, MidiRequest, SyscallState, TimeHandler};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use titan::assembler::binary::Binary;
+use titan::cpu::memory::section::SectionMemory;
+use titan::execution::executor::ExecutorMode;
+use titan::execution::trackers::empty::EmptyTracker;
+use titan::execution::Executor;
+
+// Where to grab the display while the program runs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", content = "value")]
+pub enum CapturePoint {
+    // The first time execution reaches this label.
+    Label(String),
+    Pc(u32),
+    // After the program finishes.
+    Exit,
+}
+
+impl CapturePoint {
+    // Breakpoint to stop at, None for Exit. Err if the label does not exist.
+    pub fn breakpoint(&self, labels: &HashMap<String, u32>) -> Result<Option<u32>, String> {
+        match self {
+            CapturePoint::Label(name) => labels
+                .get(name)
+                .copied()
+                .map(Some)
+                .ok_or_else(|| format!("Label {} does not exist.", name)),
+            CapturePoint::Pc(pc) => Ok(Some(*pc)),
+            CapturePoint::Exit => Ok(None),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GoldenImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>, // RGBA, like FlushDisplayState::data
+}
+
+impl GoldenImage {
+    // Any 8 bit PNG is accepted, it gets expanded to RGBA.
+    pub fn decode_png(bytes: &[u8]) -> Option<GoldenImage> {
+        let mut decoder = png::Decoder::new(bytes);
+
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+        let mut reader = decoder.read_info().ok()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).ok()?;
+
+        buffer.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            png::ColorType::Grayscale => buffer
+                .iter()
+                .flat_map(|value| [*value, *value, *value, 255])
+                .collect(),
+            png::ColorType::Indexed => return None, // EXPAND takes care of palettes
+        };
+
+        Some(GoldenImage {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    pub fn encode_png(&self) -> Option<Vec<u8>> {
+        Recording::encode_png(self.width, self.height, &self.data)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct GoldenResult {
+    pub passed: bool,
+    pub width: u32,
+    pub height: u32,
+    // Pixels with a channel further than the tolerance from the reference.
+    pub mismatched: usize,
+    // Largest channel difference seen, even if within tolerance.
+    pub max_difference: u8,
+    #[serde(skip)]
+    pub diff: Option<GoldenImage>,
+}
+
+// Mismatched pixels are drawn red over a dimmed copy of the actual frame.
+fn diff_pixel(actual: &[u8], mismatched: bool) -> [u8; 4] {
+    if mismatched {
+        [255, 0, 0, 255]
+    } else {
+        [actual[0] / 4, actual[1] / 4, actual[2] / 4, 255]
+    }
+}
+
+// Tolerance is the largest difference allowed in any channel, alpha is ignored.
+pub fn compare(actual: &GoldenImage, reference: &GoldenImage, tolerance: u8) -> GoldenResult {
+    if actual.width != reference.width || actual.height != reference.height {
+        let pixels = actual.width as usize * actual.height as usize;
+
+        // Everything counts as different, the diff is the whole frame.
+        return GoldenResult {
+            passed: false,
+            width: actual.width,
+            height: actual.height,
+            mismatched: pixels,
+            max_difference: u8::MAX,
+            diff: Some(GoldenImage {
+                width: actual.width,
+                height: actual.height,
+                data: actual
+                    .data
+                    .chunks_exact(4)
+                    .flat_map(|pixel| diff_pixel(pixel, true))
+                    .collect(),
+            }),
+        };
+    }
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+
+    let data = actual
+        .data
+        .chunks_exact(4)
+        .zip(reference.data.chunks_exact(4))
+        .flat_map(|(actual, reference)| {
+            let difference = (0..3)
+                .map(|i| actual[i].abs_diff(reference[i]))
+                .max()
+                .unwrap_or_default();
+
+            max_difference = max_difference.max(difference);
+
+            let mismatch = difference > tolerance;
+
+            if mismatch {
+                mismatched += 1;
+            }
+
+            diff_pixel(actual, mismatch)
+        })
+        .collect();
+
+    GoldenResult {
+        passed: mismatched == 0,
+        width: actual.width,
+        height: actual.height,
+        mismatched,
+        max_difference,
+        diff: if mismatched == 0 {
+            None
+        } else {
+            Some(GoldenImage {
+                width: actual.width,
+                height: actual.height,
+                data,
+            })
+        },
+    }
+}
+
+// Golden runs are headless, output and sound are dropped.
+struct SilentConsole;
+
+impl ConsoleHandler for SilentConsole {
+    fn print(&mut self, _text: &str, _error: bool) {}
+}
+
+struct SilentMidi;
+
+impl MidiHandler for SilentMidi {
+    fn play(&mut self, _request: &MidiRequest, _sync: bool) {}
+
+    fn install(&mut self, _instrument: u32) -> Pin<Box<dyn Future<Output = bool> + Send>> {
+        Box::pin(async { true })
+    }
+
+    fn installed(&mut self, _instrument: u32) -> bool {
+        true
+    }
+}
+
+// Tests run without a clock, time syscalls read nothing and sleeps return right away.
+#[cfg(test)]
+pub(crate) struct NoTime;
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl TimeHandler for NoTime {
+    fn time(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    async fn sleep(&self, _duration: std::time::Duration) {}
+}
+
+pub type GoldenDevice = ExecutionState<SectionMemory<MmioHandler>, EmptyTracker>;
+
+// A fresh run of the assembled program, the session the user is debugging is left alone.
+pub fn golden_device(
+    binary: Binary,
+    layout: &MemoryLayout,
+    time: Arc<dyn TimeHandler + Send + Sync>,
+) -> Result<GoldenDevice, String> {
+    let finished_pcs = get_binary_finished_pcs(&binary);
+
+    let mut memory = SectionMemory::new();
+    let peripherals = configure_devices(&mut memory, layout, &DeviceKind::ALL, vec![])?;
+
+    let state = state_from_binary(binary, layout, memory);
+
+    let delegate = SyscallState::new(
+        Box::new(SilentConsole),
+        Box::new(SilentMidi),
+        time,
+        None,
+        layout,
+        None,
+    );
+
+    Ok(ExecutionState {
+        debugger: Arc::new(Executor::new(state, EmptyTracker {})),
+        peripherals,
+        delegate: Arc::new(Mutex::new(delegate)),
+        finished_pcs,
+    })
+}
+
+// Runs the device until the capture point, Err if it stopped anywhere else.
+pub(crate) async fn run_to_point<Device: ExecutionDevice + ?Sized>(
+    device: &Device,
+    breakpoint: Option<u32>,
+    displays: FlushDisplays,
+) -> Result<(), String> {
+    let result = device
+        .resume(ResumeOptions {
+            batch: None,
+            breakpoints: Some(breakpoint.into_iter().collect()),
+            displays,
+            change_state: Some(ExecutorMode::Running),
+        })
+        .await
+        .map_err(|_| "Failed to run the program.".to_string())?;
+
+    match (breakpoint, result.mode) {
+        (Some(_), ResumeMode::Breakpoint) | (None, ResumeMode::Finished { .. }) => Ok(()),
+        (_, ResumeMode::Invalid { message }) => Err(message),
+        (Some(pc), _) => Err(format!(
+            "Program stopped before reaching 0x{:08x}, nothing was captured.",
+            pc
+        )),
+        (None, _) => Err("Program stopped before it finished.".to_string()),
+    }
+}
+
+// Runs the device until the capture point, then returns what the display shows.
+pub async fn capture<Device: ExecutionDevice + ?Sized>(
+    device: &Device,
+    breakpoint: Option<u32>,
+    display: FlushDisplayBody,
+) -> Result<GoldenImage, String> {
+    run_to_point(device, breakpoint, single_display(display.clone())).await?;
+
+    let display = display.lock().unwrap();
+
+    let data = display
+        .data
+        .clone()
+        .ok_or_else(|| "The display could not be read.".to_string())?;
+
+    Ok(GoldenImage {
+        width: display.width,
+        height: display.height,
+        data,
+    })
+}
+
+// Records display while the device runs to the capture point, then saves the recording to path.
+// Returns the files that were written, see Recording::save.
+pub async fn capture_recording<Device: ExecutionDevice + ?Sized>(
+    device: &Device,
+    breakpoint: Option<u32>,
+    display: FlushDisplayBody,
+    path: &Path,
+    format: RecordingFormat,
+) -> Result<Vec<PathBuf>, String> {
+    let recording = Arc::new(Mutex::new(Recording::new()));
+
+    display.lock().unwrap().recording = Some(recording.clone());
+
+    run_to_point(device, breakpoint, single_display(display)).await?;
+
+    let recording = recording.lock().unwrap();
+
+    if recording.is_empty() {
+        return Err("The display was never drawn, nothing was recorded.".to_string());
+    }
+
+    recording
+        .save(path, format)
+        .ok_or_else(|| format!("Failed to write {}.", path.display()))
+}
+
+// Runs the device until the capture point, see audio::capture_audio.
+pub async fn capture_audio<Device: ExecutionDevice + ?Sized>(
+    device: &Device,
+    breakpoint: Option<u32>,
+    path: &Path,
+) -> Result<bool, String> {
+    // Nothing is drawn, audio does not need a display.
+    let run = run_to_point(device, breakpoint, HashMap::new());
+
+    audio::capture_audio(device, run, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::assemble_text;
+    use crate::display::FlushDisplayState;
+    use crate::layout::LayoutPreset;
+    use futures::executor::block_on;
+    use std::fs;
+
+    // One red pixel in the corner of the default display.
+    const DRAW: &str = "
+        li $t0, 0x00FF0000
+        li $t1, 0x10008000
+        sw $t0, 0($t1)
+        li $v0, 10
+        syscall
+    ";
+
+    #[test]
+    fn recording_saves_a_gif_that_decodes() {
+        let binary = assemble_text(DRAW, None).unwrap_or_else(|_| panic!("Failed to assemble"));
+        let layout = LayoutPreset::default().layout();
+        let device = golden_device(binary, &layout, Arc::new(NoTime)).unwrap();
+
+        let display = Arc::new(Mutex::new(FlushDisplayState::default()));
+        let path = std::env::temp_dir().join(format!("saturn-record-{}.gif", std::process::id()));
+
+        let files = block_on(capture_recording(
+            &device,
+            None,
+            display,
+            &path,
+            RecordingFormat::Gif,
+        ))
+        .unwrap();
+
+        assert_eq!(files, vec![path.clone()]);
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+
+        let mut decoder = options.read_info(bytes.as_slice()).unwrap();
+
+        assert_eq!((decoder.width(), decoder.height()), (64, 64));
+
+        let frame = decoder
+            .read_next_frame()
+            .unwrap()
+            .expect("At least one frame");
+
+        assert_eq!(frame.buffer.len(), 64 * 64 * 4);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::display::FlushDisplayState;
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+// Tile and sprite graphics, composited in place of the bitmap display while enabled.
+pub const GRAPHICS_ADDRESS: u32 = 0xFFFF0600;
+// 256 0x00RRGGBB words, color 0 is transparent for tiles and sprites.
+pub const GRAPHICS_PALETTE: u32 = 0xFFFF2000;
+// GRAPHICS_SPRITE_COUNT entries of GRAPHICS_SPRITE_SIZE bytes:
+// x (signed half), y (signed half), tile (byte), SPRITE_* flags (byte), two unused bytes.
+pub const GRAPHICS_SPRITES: u32 = 0xFFFF2400;
+// GRAPHICS_MAP_SIZE x GRAPHICS_MAP_SIZE entries in row order: tile (byte), TILE_* flags (byte).
+pub const GRAPHICS_MAP: u32 = 0xFFFF2800;
+// 256 tiles of GRAPHICS_TILE_SIZE x GRAPHICS_TILE_SIZE palette indices in row order.
+pub const GRAPHICS_PATTERNS: u32 = 0xFFFF4000;
+
+pub const GRAPHICS_WIDTH: u32 = 256;
+pub const GRAPHICS_HEIGHT: u32 = 192;
+
+pub const GRAPHICS_TILE_SIZE: u32 = 8;
+pub const GRAPHICS_MAP_SIZE: u32 = 32; // tiles, the map wraps around when scrolled
+pub const GRAPHICS_SPRITE_COUNT: u32 = 64;
+pub const GRAPHICS_SPRITE_SIZE: u32 = 8;
+
+const PALETTE_SIZE: u32 = 256 * 4;
+const SPRITES_SIZE: u32 = GRAPHICS_SPRITE_COUNT * GRAPHICS_SPRITE_SIZE;
+const MAP_SIZE: u32 = GRAPHICS_MAP_SIZE * GRAPHICS_MAP_SIZE * 2;
+const PATTERNS_SIZE: u32 = 256 * GRAPHICS_TILE_SIZE * GRAPHICS_TILE_SIZE;
+
+// Register offsets from GRAPHICS_ADDRESS.
+// GRAPHICS_* flags.
+const GRAPHICS_CONTROL: u32 = 0x00;
+// Background scroll in pixels.
+const GRAPHICS_SCROLL_X: u32 = 0x04;
+const GRAPHICS_SCROLL_Y: u32 = 0x08;
+// Palette index shown where nothing else is drawn.
+const GRAPHICS_BACKDROP: u32 = 0x0C;
+const GRAPHICS_SIZE: u32 = 0x10;
+
+pub const GRAPHICS_ENABLE: u32 = 1 << 0;
+pub const GRAPHICS_BACKGROUND: u32 = 1 << 1;
+pub const GRAPHICS_SHOW_SPRITES: u32 = 1 << 2;
+
+pub const TILE_FLIP_X: u8 = 1 << 0;
+pub const TILE_FLIP_Y: u8 = 1 << 1;
+
+// Flips use the same bits as TILE_FLIP_*.
+pub const SPRITE_FLIP_X: u8 = 1 << 0;
+pub const SPRITE_FLIP_Y: u8 = 1 << 1;
+pub const SPRITE_VISIBLE: u8 = 1 << 2;
+pub const SPRITE_BEHIND: u8 = 1 << 3; // only drawn over transparent background pixels
+
+pub struct GraphicsState {
+    control: u32,
+    scroll_x: u32,
+    scroll_y: u32,
+    backdrop: u32,
+    palette: Vec<u8>,
+    sprites: Vec<u8>,
+    map: Vec<u8>,
+    patterns: Vec<u8>,
+}
+
+pub struct GraphicsHandler {
+    pub state: Arc<Mutex<GraphicsState>>,
+}
+
+struct Sprite {
+    x: i32,
+    y: i32,
+    tile: u8,
+    flags: u8,
+}
+
+impl Default for GraphicsState {
+    fn default() -> GraphicsState {
+        GraphicsState::new()
+    }
+}
+
+impl GraphicsState {
+    pub fn new() -> GraphicsState {
+        GraphicsState {
+            control: 0,
+            scroll_x: 0,
+            scroll_y: 0,
+            backdrop: 0,
+            palette: vec![0; PALETTE_SIZE as usize],
+            sprites: vec![0; SPRITES_SIZE as usize],
+            map: vec![0; MAP_SIZE as usize],
+            patterns: vec![0; PATTERNS_SIZE as usize],
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.control & GRAPHICS_ENABLE != 0
+    }
+
+    fn color(&self, index: u8) -> [u8; 4] {
+        let start = index as usize * 4;
+        let entry = &self.palette[start..start + 4];
+
+        // Little endian 0x00RRGGBB.
+        [entry[2], entry[1], entry[0], 255]
+    }
+
+    // Palette index of a pixel inside a tile, flips are applied here.
+    fn tile_pixel(&self, tile: u8, x: u32, y: u32, flags: u8) -> u8 {
+        let last = GRAPHICS_TILE_SIZE - 1;
+
+        let x = if flags & TILE_FLIP_X != 0 {
+            last - x
+        } else {
+            x
+        };
+        let y = if flags & TILE_FLIP_Y != 0 {
+            last - y
+        } else {
+            y
+        };
+
+        let start = tile as u32 * GRAPHICS_TILE_SIZE * GRAPHICS_TILE_SIZE;
+
+        self.patterns[(start + y * GRAPHICS_TILE_SIZE + x) as usize]
+    }
+
+    fn background_pixel(&self, x: u32, y: u32) -> u8 {
+        let span = GRAPHICS_MAP_SIZE * GRAPHICS_TILE_SIZE;
+
+        let x = x.wrapping_add(self.scroll_x) % span;
+        let y = y.wrapping_add(self.scroll_y) % span;
+
+        let entry = ((y / GRAPHICS_TILE_SIZE) * GRAPHICS_MAP_SIZE + x / GRAPHICS_TILE_SIZE) * 2;
+        let tile = self.map[entry as usize];
+        let flags = self.map[entry as usize + 1];
+
+        self.tile_pixel(tile, x % GRAPHICS_TILE_SIZE, y % GRAPHICS_TILE_SIZE, flags)
+    }
+
+    fn sprite(&self, index: u32) -> Sprite {
+        let start = (index * GRAPHICS_SPRITE_SIZE) as usize;
+        let entry = &self.sprites[start..start + GRAPHICS_SPRITE_SIZE as usize];
+
+        Sprite {
+            x: i16::from_le_bytes([entry[0], entry[1]]) as i32,
+            y: i16::from_le_bytes([entry[2], entry[3]]) as i32,
+            tile: entry[4],
+            flags: entry[5],
+        }
+    }
+
+    // Composites the background and sprites into RGBA, GRAPHICS_WIDTH x GRAPHICS_HEIGHT.
+    pub fn render(&self) -> Vec<u8> {
+        let pixels = (GRAPHICS_WIDTH * GRAPHICS_HEIGHT) as usize;
+
+        let mut indices = vec![0u8; pixels];
+        let mut background = vec![false; pixels]; // opaque background pixels
+
+        if self.control & GRAPHICS_BACKGROUND != 0 {
+            for y in 0..GRAPHICS_HEIGHT {
+                for x in 0..GRAPHICS_WIDTH {
+                    let i = (y * GRAPHICS_WIDTH + x) as usize;
+                    let index = self.background_pixel(x, y);
+
+                    indices[i] = index;
+                    background[i] = index != 0;
+                }
+            }
+        }
+
+        if self.control & GRAPHICS_SHOW_SPRITES != 0 {
+            // Lower sprites are drawn on top, so draw them last.
+            for sprite in (0..GRAPHICS_SPRITE_COUNT).rev().map(|i| self.sprite(i)) {
+                if sprite.flags & SPRITE_VISIBLE == 0 {
+                    continue;
+                }
+
+                for row in 0..GRAPHICS_TILE_SIZE {
+                    for column in 0..GRAPHICS_TILE_SIZE {
+                        let x = sprite.x + column as i32;
+                        let y = sprite.y + row as i32;
+
+                        if !(0..GRAPHICS_WIDTH as i32).contains(&x)
+                            || !(0..GRAPHICS_HEIGHT as i32).contains(&y)
+                        {
+                            continue;
+                        }
+
+                        let index = self.tile_pixel(sprite.tile, column, row, sprite.flags);
+                        let i = (y as u32 * GRAPHICS_WIDTH + x as u32) as usize;
+
+                        if index == 0 || (sprite.flags & SPRITE_BEHIND != 0 && background[i]) {
+                            continue;
+                        }
+
+                        indices[i] = index;
+                    }
+                }
+            }
+        }
+
+        let backdrop = self.color(self.backdrop as u8);
+
+        indices
+            .into_iter()
+            .flat_map(|index| {
+                if index == 0 {
+                    backdrop
+                } else {
+                    self.color(index)
+                }
+            })
+            .collect()
+    }
+
+    // Replaces the flushed display with the composited frame, returns false if disabled.
+    pub fn flush(&self, display: &mut FlushDisplayState) -> bool {
+        if !self.enabled() {
+            return false;
+        }
+
+        display.width = GRAPHICS_WIDTH;
+        display.height = GRAPHICS_HEIGHT;
+        display.data = Some(self.render());
+
+        true
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            GRAPHICS_CONTROL => self.control,
+            GRAPHICS_SCROLL_X => self.scroll_x,
+            GRAPHICS_SCROLL_Y => self.scroll_y,
+            GRAPHICS_BACKDROP => self.backdrop,
+            _ => 0,
+        }
+    }
+
+    fn register(&mut self, offset: u32) -> Option<&mut u32> {
+        match offset {
+            GRAPHICS_CONTROL => Some(&mut self.control),
+            GRAPHICS_SCROLL_X => Some(&mut self.scroll_x),
+            GRAPHICS_SCROLL_Y => Some(&mut self.scroll_y),
+            GRAPHICS_BACKDROP => Some(&mut self.backdrop),
+            _ => None,
+        }
+    }
+
+    // Pattern, map, sprite and palette memory, with the offset into it.
+    fn region(&mut self, address: u32) -> Option<(&mut Vec<u8>, usize)> {
+        let regions = [
+            (GRAPHICS_PALETTE, PALETTE_SIZE),
+            (GRAPHICS_SPRITES, SPRITES_SIZE),
+            (GRAPHICS_MAP, MAP_SIZE),
+            (GRAPHICS_PATTERNS, PATTERNS_SIZE),
+        ];
+
+        let (start, _) = regions
+            .into_iter()
+            .find(|(start, size)| (*start..*start + *size).contains(&address))?;
+
+        let offset = (address - start) as usize;
+
+        let memory = match start {
+            GRAPHICS_PALETTE => &mut self.palette,
+            GRAPHICS_SPRITES => &mut self.sprites,
+            GRAPHICS_MAP => &mut self.map,
+            _ => &mut self.patterns,
+        };
+
+        Some((memory, offset))
+    }
+}
+
+impl GraphicsHandler {
+    pub fn new() -> GraphicsHandler {
+        GraphicsHandler {
+            state: Arc::new(Mutex::new(GraphicsState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (GRAPHICS_ADDRESS..GRAPHICS_ADDRESS + GRAPHICS_SIZE).contains(&address)
+            || (GRAPHICS_PALETTE..GRAPHICS_PATTERNS + PATTERNS_SIZE).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some((memory, offset)) = state.region(address) {
+            return Ok(memory[offset]);
+        }
+
+        let offset = address.wrapping_sub(GRAPHICS_ADDRESS);
+
+        if offset < GRAPHICS_SIZE {
+            Ok(read_register_byte(state.read_register(offset & !3), offset))
+        } else if Self::contains(address) {
+            Ok(0) // gaps between regions
+        } else {
+            Err(MemoryUnmapped(address))
+        }
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some((memory, offset)) = state.region(address) {
+            memory[offset] = value;
+
+            return Ok(());
+        }
+
+        let offset = address.wrapping_sub(GRAPHICS_ADDRESS);
+
+        if let Some(register) = state.register(offset & !3) {
+            *register = write_register_byte(*register, offset, value);
+
+            Ok(())
+        } else if Self::contains(address) {
+            Ok(())
+        } else {
+            Err(MemoryUnmapped(address))
+        }
+    }
+}
+
+impl MmioDevice for GraphicsHandler {
+    fn name(&self) -> &str {
+        "graphics"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![
+            GRAPHICS_ADDRESS..GRAPHICS_ADDRESS + GRAPHICS_SIZE,
+            GRAPHICS_PALETTE..GRAPHICS_PATTERNS + PATTERNS_SIZE,
+        ]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = GraphicsState::new()
+    }
+}
@@ -0,0 +1,214 @@
+use crate::layout::MemoryLayout;
+use serde::Serialize;
+use titan::cpu::Memory;
+
+// sbrk rounds every request up to a multiple of this, so the break stays word aligned.
+pub const HEAP_ALIGNMENT: u32 = 4;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HeapAllocation {
+    pub address: u32,
+    pub size: u32,      // after alignment
+    pub requested: u32, // what the program asked for
+    pub pc: u32,        // the syscall instruction
+}
+
+// The program break, sbrk moves it between the start and the limit of the layout's heap.
+pub struct ProgramBreak {
+    start: u32,
+    limit: u32,
+    current: u32,
+    high_water: u32,
+    allocations: Vec<HeapAllocation>,
+}
+
+fn align(value: u32) -> Option<u32> {
+    value
+        .checked_add(HEAP_ALIGNMENT - 1)
+        .map(|value| value & !(HEAP_ALIGNMENT - 1))
+}
+
+impl ProgramBreak {
+    pub fn new(layout: &MemoryLayout) -> ProgramBreak {
+        let start = align(layout.heap_start).unwrap_or(layout.heap_start);
+
+        ProgramBreak {
+            start,
+            limit: layout.heap_limit.max(start),
+            current: start,
+            high_water: start,
+            allocations: vec![],
+        }
+    }
+
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    // First address past the heap.
+    pub fn current(&self) -> u32 {
+        self.current
+    }
+
+    pub fn allocations(&self) -> &[HeapAllocation] {
+        &self.allocations
+    }
+
+    // Returns the old break. Negative amounts give memory back, allocations past
+    // the new break are forgotten.
+    pub fn sbrk(&mut self, amount: i32, pc: u32) -> Result<u32, String> {
+        let previous = self.current;
+
+        if amount < 0 {
+            let size = align(amount.unsigned_abs()).unwrap_or(u32::MAX);
+
+            let Some(current) = previous.checked_sub(size).filter(|x| *x >= self.start) else {
+                return Err(format!(
+                    "sbrk({}) would move the break below the start of the heap (0x{:08x}).",
+                    amount, self.start
+                ));
+            };
+
+            self.current = current;
+            self.allocations
+                .retain(|allocation| allocation.address < current);
+
+            if let Some(last) = self.allocations.last_mut() {
+                last.size = last.size.min(current - last.address);
+            }
+
+            return Ok(previous);
+        }
+
+        let requested = amount as u32;
+
+        let current = align(requested)
+            .and_then(|size| previous.checked_add(size))
+            .filter(|current| *current <= self.limit);
+
+        let Some(current) = current else {
+            return Err(format!(
+                "Out of memory: sbrk({}) does not fit in the {} bytes left before 0x{:08x}.",
+                amount,
+                self.limit - previous,
+                self.limit
+            ));
+        };
+
+        if current > previous {
+            self.allocations.push(HeapAllocation {
+                address: previous,
+                size: current - previous,
+                requested,
+                pc,
+            });
+        }
+
+        self.current = current;
+        self.high_water = self.high_water.max(current);
+
+        Ok(previous)
+    }
+}
+
+// Bytes of each allocation copied into the report.
+pub const HEAP_PREVIEW_SIZE: u32 = 16;
+// Words of each allocation checked for pointers, large arrays are cut short.
+pub const HEAP_POINTER_SCAN: u32 = 256;
+
+#[derive(Clone, Serialize)]
+pub struct HeapPointer {
+    pub offset: u32, // from the start of the allocation holding it
+    pub target: u32, // address of the allocation it points into
+}
+
+#[derive(Clone, Serialize)]
+pub struct HeapBlock {
+    #[serde(flatten)]
+    pub allocation: HeapAllocation,
+    pub preview: Vec<Option<u8>>,
+    // Aligned words holding the address of an allocation, the links of lists and trees.
+    pub pointers: Vec<HeapPointer>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct HeapReport {
+    pub start: u32,
+    pub limit: u32,
+    pub current: u32,    // the break
+    pub high_water: u32, // highest the break has been
+    pub stack_pointer: u32,
+    pub stack_low_water: u32, // lowest $sp seen, sampled between batches
+    // Bytes between the highest break and the lowest $sp.
+    pub closest: u32,
+    // True once closest drops under a tenth of what was free when the program started.
+    pub approaching: bool,
+    pub blocks: Vec<HeapBlock>,
+}
+
+impl ProgramBreak {
+    fn owner(&self, address: u32) -> Option<&HeapAllocation> {
+        let index = self
+            .allocations
+            .partition_point(|allocation| allocation.address <= address);
+
+        self.allocations[..index]
+            .last()
+            .filter(|allocation| address < allocation.address + allocation.size)
+    }
+
+    fn block<Mem: Memory>(&self, allocation: &HeapAllocation, memory: &Mem) -> HeapBlock {
+        let preview = (0..allocation.size.min(HEAP_PREVIEW_SIZE))
+            .map(|offset| memory.get(allocation.address + offset).ok())
+            .collect();
+
+        let pointers = (0..(allocation.size / 4).min(HEAP_POINTER_SCAN))
+            .filter_map(|word| {
+                let offset = word * 4;
+                let value = memory.get_u32(allocation.address + offset).ok()?;
+
+                Some(HeapPointer {
+                    offset,
+                    target: self.owner(value)?.address,
+                })
+            })
+            .collect();
+
+        HeapBlock {
+            allocation: allocation.clone(),
+            preview,
+            pointers,
+        }
+    }
+
+    pub fn report<Mem: Memory>(
+        &self,
+        stack_pointer: u32,
+        stack_low_water: u32,
+        initial_stack_pointer: u32,
+        memory: &Mem,
+    ) -> HeapReport {
+        let closest = stack_low_water.saturating_sub(self.high_water);
+        let initial = initial_stack_pointer.saturating_sub(self.start);
+
+        HeapReport {
+            start: self.start,
+            limit: self.limit,
+            current: self.current,
+            high_water: self.high_water,
+            stack_pointer,
+            stack_low_water,
+            closest,
+            approaching: (closest as u64) * 10 < initial as u64,
+            blocks: self
+                .allocations
+                .iter()
+                .map(|allocation| self.block(allocation, memory))
+                .collect(),
+        }
+    }
+}
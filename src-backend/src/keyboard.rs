@@ -1,3 +1,7 @@
+use crate::mmio::read_register_byte;
+use crate::registry::MmioDevice;
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use titan::cpu::error;
 use titan::cpu::error::Error::MemoryUnmapped;
@@ -5,22 +9,49 @@ use titan::cpu::memory::section::ListenResponder;
 
 pub const KEYBOARD_ADDRESS: u32 = 0xFFFF0000;
 pub const KEYBOARD_HOLDING: u32 = 0xFFFF0080;
+// Read: bit 0 is set if a key was dropped because the queue was full,
+// bits 8 to 15 hold the number of queued keys. Any write clears the overflow bit.
+pub const KEYBOARD_STATUS: u32 = 0xFFFF0040;
+// Read/write: bit 0 set (default) repeats the last key when the queue is empty, otherwise 0 is read.
+pub const KEYBOARD_MODE: u32 = 0xFFFF0044;
 pub const KEYBOARD_SELECTOR: u32 = KEYBOARD_ADDRESS >> 16;
+pub const KEYBOARD_INTERRUPT: u32 = 1 << 8; // Cause IP0, same as MARS
+pub const KEYBOARD_QUEUE_SIZE: usize = 16;
+
+// Receiver control bits at KEYBOARD_ADDRESS.
+const KEYBOARD_READY: u8 = 1 << 0;
+const KEYBOARD_INTERRUPT_ENABLE: u8 = 1 << 1;
+
+const KEYBOARD_OVERFLOW: u32 = 1 << 0;
+const KEYBOARD_REPEAT: u32 = 1 << 0;
 
 pub struct KeyboardState {
     last: Option<char>,
-    keys: Vec<char>,
+    keys: VecDeque<char>,
     holding: [bool; 128],
+    interrupt_enabled: bool,
+    overflow: bool,
+    repeat: bool,
 }
 
 pub struct KeyboardHandler {
     pub state: Arc<Mutex<KeyboardState>>,
 }
 
+impl Default for KeyboardState {
+    fn default() -> KeyboardState {
+        KeyboardState::new()
+    }
+}
+
 impl KeyboardState {
     pub fn push_key(&mut self, key: char, up: bool) {
         if !up {
-            self.keys.push(key)
+            if self.keys.len() < KEYBOARD_QUEUE_SIZE {
+                self.keys.push_back(key)
+            } else {
+                self.overflow = true
+            }
         }
 
         let offset = key as usize;
@@ -30,19 +61,46 @@ impl KeyboardState {
         }
     }
 
+    pub fn pending(&self) -> bool {
+        self.interrupt_enabled && !self.keys.is_empty()
+    }
+
     fn pop_key(&mut self) -> Option<char> {
-        if let Some(value) = self.keys.pop() {
-            self.last = Some(value)
+        if let Some(value) = self.keys.pop_front() {
+            self.last = Some(value);
+
+            return self.last;
         }
 
-        self.last
+        if self.repeat {
+            self.last
+        } else {
+            None
+        }
+    }
+
+    fn status(&self) -> u32 {
+        let overflow = if self.overflow { KEYBOARD_OVERFLOW } else { 0 };
+
+        overflow | ((self.keys.len() as u32) << 8)
+    }
+
+    fn mode(&self) -> u32 {
+        if self.repeat {
+            KEYBOARD_REPEAT
+        } else {
+            0
+        }
     }
 
     pub fn new() -> KeyboardState {
         KeyboardState {
             last: None,
-            keys: vec![],
+            keys: VecDeque::with_capacity(KEYBOARD_QUEUE_SIZE),
             holding: [false; 128],
+            interrupt_enabled: false,
+            overflow: false,
+            repeat: true,
         }
     }
 }
@@ -59,6 +117,7 @@ impl ListenResponder for KeyboardHandler {
     fn read(&self, address: u32) -> error::Result<u8> {
         let keyboard_handled = KEYBOARD_ADDRESS..KEYBOARD_ADDRESS + 8;
         let keyboard_holding = KEYBOARD_HOLDING..KEYBOARD_HOLDING + 128;
+        let keyboard_status = KEYBOARD_STATUS..KEYBOARD_MODE + 4;
 
         if keyboard_handled.contains(&address) {
             let offset = address - KEYBOARD_ADDRESS;
@@ -67,15 +126,33 @@ impl ListenResponder for KeyboardHandler {
 
             Ok(match offset {
                 0 => {
-                    if state.keys.is_empty() {
+                    let ready = if state.keys.is_empty() {
                         0
                     } else {
-                        1
-                    }
+                        KEYBOARD_READY
+                    };
+
+                    let enabled = if state.interrupt_enabled {
+                        KEYBOARD_INTERRUPT_ENABLE
+                    } else {
+                        0
+                    };
+
+                    ready | enabled
                 }
                 4 => state.pop_key().map(|c| c as u8).unwrap_or(0),
                 _ => 0,
             })
+        } else if keyboard_status.contains(&address) {
+            let state = self.state.lock().unwrap();
+
+            let value = if address < KEYBOARD_MODE {
+                state.status()
+            } else {
+                state.mode()
+            };
+
+            Ok(read_register_byte(value, address))
         } else if keyboard_holding.contains(&address) {
             let offset = address - KEYBOARD_HOLDING;
 
@@ -91,7 +168,60 @@ impl ListenResponder for KeyboardHandler {
         }
     }
 
-    fn write(&mut self, address: u32, _: u8) -> error::Result<()> {
-        Err(MemoryUnmapped(address))
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        // Only the interrupt enable bit is writable, ready is controlled by the keyboard.
+        if address == KEYBOARD_ADDRESS {
+            self.state.lock().unwrap().interrupt_enabled = value & KEYBOARD_INTERRUPT_ENABLE != 0;
+
+            Ok(())
+        } else if (KEYBOARD_ADDRESS..KEYBOARD_ADDRESS + 4).contains(&address) {
+            Ok(())
+        } else if (KEYBOARD_STATUS..KEYBOARD_MODE).contains(&address) {
+            self.state.lock().unwrap().overflow = false;
+
+            Ok(())
+        } else if address == KEYBOARD_MODE {
+            self.state.lock().unwrap().repeat = value as u32 & KEYBOARD_REPEAT != 0;
+
+            Ok(())
+        } else if (KEYBOARD_MODE..KEYBOARD_MODE + 4).contains(&address) {
+            Ok(())
+        } else {
+            Err(MemoryUnmapped(address))
+        }
+    }
+}
+
+impl MmioDevice for KeyboardHandler {
+    fn name(&self) -> &str {
+        "keyboard"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![
+            KEYBOARD_ADDRESS..KEYBOARD_ADDRESS + 8,
+            KEYBOARD_STATUS..KEYBOARD_MODE + 4,
+            KEYBOARD_HOLDING..KEYBOARD_HOLDING + 128,
+        ]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        ListenResponder::read(self, address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        ListenResponder::write(self, address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = KeyboardState::new()
+    }
+
+    fn pending_interrupt(&self) -> u32 {
+        if self.state.lock().unwrap().pending() {
+            KEYBOARD_INTERRUPT
+        } else {
+            0
+        }
     }
 }
@@ -0,0 +1,217 @@
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use serde::Serialize;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use titan::cpu::memory::section::ListenResponder;
+
+// Seven-segment displays, hex keypad, LEDs and switches, like the MARS Digital Lab Sim.
+// The first five bytes are at the same addresses as in MARS.
+pub const LAB_ADDRESS: u32 = 0xFFFF0010;
+
+// Byte offsets from LAB_ADDRESS.
+// Read/write: bits 0 to 6 light segments a to g, bit 7 lights the dot.
+const LAB_RIGHT_SEGMENT: u32 = 0x00;
+const LAB_LEFT_SEGMENT: u32 = 0x01;
+// Write: bits 0 to 3 pick the keypad rows LAB_KEYPAD_SCAN looks at.
+const LAB_KEYPAD_ROW: u32 = 0x02;
+// The MARS counter interrupt, writes are accepted but the timer device should be used instead.
+const LAB_COUNTER: u32 = 0x03;
+// Read: the row bit in the low nibble and the column bit in the high nibble
+// of a key held in one of the selected rows, 0 if there is none.
+const LAB_KEYPAD_SCAN: u32 = 0x04;
+// Word registers.
+// Read/write: one bit per LED, bit 0 is the rightmost.
+const LAB_LEDS: u32 = 0x08;
+// Read only: one bit per switch that is on, bit 0 is the rightmost.
+const LAB_SWITCHES: u32 = 0x0C;
+const LAB_SIZE: u32 = 0x10;
+
+pub const LAB_KEY_COUNT: u8 = 16;
+pub const LAB_LED_COUNT: u8 = 8;
+pub const LAB_SWITCH_COUNT: u8 = 8;
+
+const KEYPAD_COLUMNS: u8 = 4;
+
+#[derive(Clone, Serialize)]
+pub struct LabDisplay {
+    pub left: u8,  // segment bits of the left display
+    pub right: u8, // segment bits of the right display
+    pub leds: u32,
+    pub switches: u32,
+    pub keys: u16, // bit per held keypad key, key 0 is bit 0
+}
+
+pub struct LabState {
+    left: u8,
+    right: u8,
+    rows: u8,
+    leds: u32,
+    switches: u32,
+    keys: u16,
+}
+
+pub struct LabHandler {
+    pub state: Arc<Mutex<LabState>>,
+}
+
+impl Default for LabState {
+    fn default() -> LabState {
+        LabState::new()
+    }
+}
+
+impl LabState {
+    pub fn new() -> LabState {
+        LabState {
+            left: 0,
+            right: 0,
+            rows: 0,
+            leds: 0,
+            switches: 0,
+            keys: 0,
+        }
+    }
+
+    // Key is the value on the keypad, 0x0 to 0xF.
+    pub fn post_keypad(&mut self, key: u8, up: bool) {
+        if key >= LAB_KEY_COUNT {
+            return;
+        }
+
+        if up {
+            self.keys &= !(1 << key);
+        } else {
+            self.keys |= 1 << key;
+        }
+    }
+
+    pub fn post_switch(&mut self, index: u8, on: bool) {
+        if index >= LAB_SWITCH_COUNT {
+            return;
+        }
+
+        if on {
+            self.switches |= 1 << index;
+        } else {
+            self.switches &= !(1 << index);
+        }
+    }
+
+    pub fn snapshot(&self) -> LabDisplay {
+        LabDisplay {
+            left: self.left,
+            right: self.right,
+            leds: self.leds,
+            switches: self.switches,
+            keys: self.keys,
+        }
+    }
+
+    // Keys are laid out four to a row, 0 to 3 in the first row.
+    fn scan(&self) -> u8 {
+        (0..LAB_KEY_COUNT)
+            .filter(|key| self.keys & (1 << key) != 0)
+            .map(|key| {
+                let row = key / KEYPAD_COLUMNS;
+                let column = key % KEYPAD_COLUMNS;
+
+                (1 << row, 0x10 << column)
+            })
+            .find(|(row, _)| self.rows & row != 0)
+            .map(|(row, column)| row | column)
+            .unwrap_or(0)
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            LAB_LEDS => self.leds,
+            LAB_SWITCHES => self.switches,
+            _ => 0,
+        }
+    }
+}
+
+impl LabHandler {
+    pub fn new() -> LabHandler {
+        LabHandler {
+            state: Arc::new(Mutex::new(LabState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (LAB_ADDRESS..LAB_ADDRESS + LAB_SIZE).contains(&address)
+    }
+}
+
+impl ListenResponder for LabHandler {
+    fn read(&self, address: u32) -> error::Result<u8> {
+        let offset = address.wrapping_sub(LAB_ADDRESS);
+
+        if offset >= LAB_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let state = self.state.lock().unwrap();
+
+        Ok(match offset {
+            LAB_RIGHT_SEGMENT => state.right,
+            LAB_LEFT_SEGMENT => state.left,
+            LAB_KEYPAD_ROW => state.rows,
+            LAB_KEYPAD_SCAN => state.scan(),
+            _ => read_register_byte(state.read_register(offset & !3), offset),
+        })
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(LAB_ADDRESS);
+
+        if offset >= LAB_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        match offset {
+            LAB_RIGHT_SEGMENT => state.right = value,
+            LAB_LEFT_SEGMENT => state.left = value,
+            LAB_KEYPAD_ROW => state.rows = value & 0xF,
+            LAB_COUNTER => {}
+            _ if offset & !3 == LAB_LEDS => {
+                state.leds =
+                    write_register_byte(state.leds, offset, value) & ((1 << LAB_LED_COUNT) - 1)
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl MmioDevice for LabHandler {
+    fn name(&self) -> &str {
+        "lab"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![LAB_ADDRESS..LAB_ADDRESS + LAB_SIZE]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        ListenResponder::read(self, address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        ListenResponder::write(self, address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = LabState::new()
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self.state.lock().unwrap().snapshot()).ok()
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use titan::cpu::memory::{Mountable, Region};
+use titan::cpu::{Memory, State};
+
+// Everything placed at run time: the stack, the $gp area, the heap and the writable range.
+// Segment addresses (.text, .data) come from the assembler or the ELF and are not affected.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MemoryLayout {
+    pub stack_pointer: u32,  // initial $sp
+    pub stack_size: u32,     // bytes mounted below the stack pointer
+    pub global_pointer: u32, // initial $gp
+    pub global_start: u32,   // zeroed region for globals, the bitmap display reads here by default
+    pub global_size: u32,
+    pub heap_start: u32, // first address sbrk hands out
+    pub heap_limit: u32, // sbrk fails instead of growing past this
+    // Unmounted addresses in here read as 0xCC until written, rounded out to whole selectors.
+    pub writable_start: u32,
+    pub writable_end: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutPreset {
+    // What Saturn always used, sbrk starts far away from the data segment.
+    #[default]
+    Saturn,
+    // MARS "Default", the assembler already places .text and .data where MARS does.
+    // MARS compact (data at 0) and SPIM (data at 0x10000000) are not offered: the assembler
+    // resolves labels against fixed segment bases, and a binary can't be moved after assembly.
+    MarsDefault,
+}
+
+impl LayoutPreset {
+    pub fn layout(self) -> MemoryLayout {
+        match self {
+            LayoutPreset::Saturn => MemoryLayout {
+                stack_pointer: 0x7FFFFFFC,
+                stack_size: 0x100000,
+                global_pointer: 0x10008000,
+                global_start: 0x10008000,
+                global_size: 0x8000,
+                heap_start: 0x20000000,
+                heap_limit: 0x7FF00000,
+                writable_start: 0x10000000,
+                writable_end: 0x80000000,
+            },
+            LayoutPreset::MarsDefault => MemoryLayout {
+                stack_pointer: 0x7FFFEFFC,
+                stack_size: 0x100000,
+                global_pointer: 0x10008000,
+                global_start: 0x10000000,
+                global_size: 0x10000,
+                heap_start: 0x10040000,
+                heap_limit: 0x7FEFF000,
+                writable_start: 0x10000000,
+                writable_end: 0x80000000,
+            },
+        }
+    }
+}
+
+impl Default for MemoryLayout {
+    fn default() -> MemoryLayout {
+        LayoutPreset::default().layout()
+    }
+}
+
+impl MemoryLayout {
+    // Lowest address of the stack region, the word at the stack pointer is part of the stack.
+    pub fn stack_bottom(&self) -> u32 {
+        self.stack_pointer
+            .wrapping_add(4)
+            .wrapping_sub(self.stack_size)
+    }
+
+    pub fn stack_region(&self) -> Region {
+        Region {
+            start: self.stack_bottom(),
+            data: vec![0; self.stack_size as usize],
+        }
+    }
+
+    pub fn global_region(&self) -> Region {
+        Region {
+            start: self.global_start,
+            data: vec![0; self.global_size as usize],
+        }
+    }
+
+    pub fn writable_selectors(&self) -> Range<u32> {
+        let end = (self.writable_end as u64 + 0xFFFF) >> 16;
+
+        (self.writable_start >> 16)..end as u32
+    }
+
+    // Mounts the stack and global regions and points $sp and $gp at them.
+    pub fn apply<Mem: Memory + Mountable>(&self, state: &mut State<Mem>) {
+        state.memory.mount(self.stack_region());
+        state.memory.mount(self.global_region());
+
+        state.registers.line[29] = self.stack_pointer;
+        state.registers.line[28] = self.global_pointer;
+    }
+}
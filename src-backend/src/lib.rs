@@ -1,12 +1,34 @@
+pub mod audio;
 pub mod build;
 pub mod channels;
+pub mod checker;
+pub mod convention;
 pub mod decode;
 pub mod device;
 pub mod display;
+pub mod effects;
+pub mod exception;
 pub mod execution;
+pub mod golden;
+pub mod graphics;
+pub mod heap;
 pub mod hex_format;
 pub mod keyboard;
+pub mod lab;
+pub mod layout;
 pub mod midi;
+pub mod mmio;
+pub mod mouse;
+pub mod recording;
 pub mod regions;
+pub mod registry;
+pub mod scancode;
+pub mod screen;
+pub mod shadow;
 pub mod shortcuts;
+pub mod stack;
 pub mod syscall;
+pub mod text;
+pub mod timer;
+pub mod transmitter;
+pub mod uart;
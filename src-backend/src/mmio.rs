@@ -0,0 +1,100 @@
+use crate::registry::{DeviceRegistry, DeviceRegistryBody};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::memory::section::ListenResponder;
+use titan::cpu::{Memory, State};
+
+// Device registers are accessed a byte at a time, assuming little endian.
+pub fn read_register_byte(value: u32, address: u32) -> u8 {
+    value.wrapping_shr((address & 3) * 8) as u8
+}
+
+pub fn write_register_byte(value: u32, address: u32, byte: u8) -> u32 {
+    let shift = (address & 3) * 8;
+
+    (value & !(0xFFu32 << shift)) | ((byte as u32) << shift)
+}
+
+// Shared handles to the state of every device behind MmioHandler.
+// States are keyed by their type and created on first use, a new device needs no changes here.
+#[derive(Clone)]
+pub struct Peripherals {
+    states: Arc<Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>>,
+    pub devices: DeviceRegistryBody, // only the devices in here are mapped
+}
+
+impl Peripherals {
+    // Nothing is registered yet, see DeviceKind::create.
+    pub fn new() -> Peripherals {
+        Peripherals {
+            states: Arc::new(Mutex::new(HashMap::new())),
+            devices: Arc::new(Mutex::new(DeviceRegistry::new())),
+        }
+    }
+
+    // The state shared by a device's handler and the host, the same handle every time.
+    pub fn get<T: Default + Send + 'static>(&self) -> Arc<Mutex<T>> {
+        let state = self
+            .states
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Arc::new(Mutex::new(T::default())))
+            .clone();
+
+        state
+            .downcast::<Mutex<T>>()
+            .expect("States are keyed by their own type")
+    }
+
+    pub fn with<T: Default + Send + 'static, R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.get::<T>().lock().unwrap())
+    }
+
+    // The hooks below go through every registered device, see MmioDevice.
+    pub fn tick(&self, instructions: u32, now: Option<Duration>) {
+        self.devices.lock().unwrap().tick(instructions, now)
+    }
+
+    // Lets the device that rang a doorbell at address handle it, false if it was a real fault.
+    pub fn present<Mem: Memory>(&self, state: &mut State<Mem>, address: u32) -> bool {
+        self.devices
+            .lock()
+            .unwrap()
+            .present(address, &mut state.memory)
+    }
+
+    // Largest batch that will not step over a device changing state.
+    pub fn slice(&self, count: u32) -> u32 {
+        self.devices.lock().unwrap().slice(count)
+    }
+
+    // Cause register IP bits for every device requesting an interrupt.
+    pub fn pending_interrupts(&self) -> u32 {
+        self.devices.lock().unwrap().pending_interrupts()
+    }
+}
+
+// Routes the MMIO selector to whichever registered device claims the address.
+pub struct MmioHandler {
+    devices: DeviceRegistryBody,
+}
+
+impl MmioHandler {
+    pub fn new(devices: DeviceRegistryBody) -> MmioHandler {
+        MmioHandler { devices }
+    }
+}
+
+impl ListenResponder for MmioHandler {
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.devices.lock().unwrap().read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.devices.lock().unwrap().write(address, value)
+    }
+}
@@ -0,0 +1,176 @@
+use crate::mmio::read_register_byte;
+use crate::registry::MmioDevice;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+pub const MOUSE_ADDRESS: u32 = 0xFFFF0300;
+
+// Register offsets from MOUSE_ADDRESS, positions are in display pixels.
+// Read: pointer position, negative when the pointer is left of or above the display.
+const MOUSE_X: u32 = 0x00;
+const MOUSE_Y: u32 = 0x04;
+// Read: buttons currently held, see MOUSE_*.
+const MOUSE_BUTTONS: u32 = 0x08;
+// Read: bits 0 to 7 hold the number of queued clicks, bit 31 is set if a click was dropped.
+// Any write clears the overflow bit.
+const MOUSE_CLICK_STATUS: u32 = 0x0C;
+// Read: reading the lowest byte moves the next click into the click registers
+// and returns the button that was pressed, or 0 if there were no clicks.
+const MOUSE_CLICK_BUTTON: u32 = 0x10;
+const MOUSE_CLICK_X: u32 = 0x14;
+const MOUSE_CLICK_Y: u32 = 0x18;
+const MOUSE_SIZE: u32 = 0x1C;
+
+pub const MOUSE_LEFT: u32 = 1 << 0;
+pub const MOUSE_RIGHT: u32 = 1 << 1;
+pub const MOUSE_MIDDLE: u32 = 1 << 2;
+
+pub const MOUSE_QUEUE_SIZE: usize = 16;
+
+const STATUS_OVERFLOW: u32 = 1 << 31;
+
+#[derive(Copy, Clone)]
+struct Click {
+    button: u32,
+    x: i32,
+    y: i32,
+}
+
+pub struct MouseState {
+    x: i32,
+    y: i32,
+    buttons: u32,
+    clicks: VecDeque<Click>,
+    current: Option<Click>,
+    overflow: bool,
+}
+
+pub struct MouseHandler {
+    pub state: Arc<Mutex<MouseState>>,
+}
+
+impl Default for MouseState {
+    fn default() -> MouseState {
+        MouseState::new()
+    }
+}
+
+impl MouseState {
+    pub fn new() -> MouseState {
+        MouseState {
+            x: 0,
+            y: 0,
+            buttons: 0,
+            clicks: VecDeque::with_capacity(MOUSE_QUEUE_SIZE),
+            current: None,
+            overflow: false,
+        }
+    }
+
+    pub fn post_mouse(&mut self, x: i32, y: i32, buttons: u32) {
+        let pressed = buttons & !self.buttons;
+
+        self.x = x;
+        self.y = y;
+        self.buttons = buttons;
+
+        // One click per newly pressed button.
+        for button in [MOUSE_LEFT, MOUSE_RIGHT, MOUSE_MIDDLE] {
+            if pressed & button == 0 {
+                continue;
+            }
+
+            if self.clicks.len() < MOUSE_QUEUE_SIZE {
+                self.clicks.push_back(Click { button, x, y })
+            } else {
+                self.overflow = true
+            }
+        }
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        let current = self.current.as_ref();
+
+        match offset {
+            MOUSE_X => self.x as u32,
+            MOUSE_Y => self.y as u32,
+            MOUSE_BUTTONS => self.buttons,
+            MOUSE_CLICK_STATUS => {
+                let overflow = if self.overflow { STATUS_OVERFLOW } else { 0 };
+
+                overflow | self.clicks.len() as u32
+            }
+            MOUSE_CLICK_BUTTON => current.map(|click| click.button).unwrap_or(0),
+            MOUSE_CLICK_X => current.map(|click| click.x as u32).unwrap_or(0),
+            MOUSE_CLICK_Y => current.map(|click| click.y as u32).unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
+impl MouseHandler {
+    pub fn new() -> MouseHandler {
+        MouseHandler {
+            state: Arc::new(Mutex::new(MouseState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (MOUSE_ADDRESS..MOUSE_ADDRESS + MOUSE_SIZE).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let offset = address.wrapping_sub(MOUSE_ADDRESS);
+
+        if offset >= MOUSE_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        if offset == MOUSE_CLICK_BUTTON {
+            state.current = state.clicks.pop_front();
+        }
+
+        Ok(read_register_byte(state.read_register(offset & !3), offset))
+    }
+
+    pub fn write(&mut self, address: u32, _: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(MOUSE_ADDRESS);
+
+        match offset {
+            MOUSE_CLICK_STATUS..MOUSE_CLICK_BUTTON => {
+                self.state.lock().unwrap().overflow = false;
+
+                Ok(())
+            }
+            _ if Self::contains(address) => Ok(()),
+            _ => Err(MemoryUnmapped(address)),
+        }
+    }
+}
+
+impl MmioDevice for MouseHandler {
+    fn name(&self) -> &str {
+        "mouse"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![MOUSE_ADDRESS..MOUSE_ADDRESS + MOUSE_SIZE]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = MouseState::new()
+    }
+}
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Frames are captured at most this often while the program runs.
+pub const RECORDING_INTERVAL: Duration = Duration::from_millis(33);
+
+// Keeps a forgotten recording from eating all the memory, frames past this many bytes are dropped.
+pub const RECORDING_MAX_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingFormat {
+    Gif,
+    PngSequence,
+}
+
+struct Frame {
+    width: u32,
+    height: u32,
+    data: Vec<u8>, // RGBA, like FlushDisplayState::data
+    start: Option<Duration>,
+    duration: Duration,
+}
+
+impl Frame {
+    fn valid(&self) -> bool {
+        frame_valid(self.width, self.height, &self.data)
+    }
+}
+
+// RGBA data has to cover the whole frame, the encoders panic otherwise.
+fn frame_valid(width: u32, height: u32, data: &[u8]) -> bool {
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4));
+
+    width > 0 && height > 0 && size == Some(data.len())
+}
+
+#[derive(Default)]
+pub struct Recording {
+    frames: Vec<Frame>,
+    bytes: usize, // frame data held, bounded by RECORDING_MAX_BYTES
+    last: Option<Duration>,
+    dropped: bool,
+}
+
+pub type RecordingBody = Arc<Mutex<Recording>>;
+
+impl Recording {
+    pub fn new() -> Recording {
+        Recording::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // True if frames were dropped because the recording got too long.
+    pub fn dropped(&self) -> bool {
+        self.dropped
+    }
+
+    // True if enough time has passed since the last frame to capture another.
+    pub fn due(&self, now: Option<Duration>) -> bool {
+        match (self.last, now) {
+            (Some(last), Some(now)) => now.saturating_sub(last) >= RECORDING_INTERVAL,
+            _ => true,
+        }
+    }
+
+    // Time is the wall clock time of the frame, used for frame delays.
+    pub fn push(&mut self, width: u32, height: u32, data: &[u8], now: Option<Duration>) {
+        // A frame read while the display changed size, the next flush has the right one.
+        if !frame_valid(width, height, data) {
+            return;
+        }
+
+        // Too soon for a new frame, but the latest contents should still win.
+        if !self.due(now) {
+            if let Some(previous) = self.frames.last_mut() {
+                let bytes = self.bytes - previous.data.len() + data.len();
+
+                if bytes <= RECORDING_MAX_BYTES {
+                    previous.width = width;
+                    previous.height = height;
+                    previous.data = data.to_vec();
+
+                    self.bytes = bytes
+                }
+            }
+
+            return;
+        }
+
+        self.last = now.or(self.last);
+
+        // The previous frame stays on screen until this one shows up.
+        if let Some(previous) = self.frames.last_mut() {
+            previous.duration = match (previous.start, now) {
+                (Some(start), Some(now)) => now.saturating_sub(start),
+                _ => previous.duration + RECORDING_INTERVAL,
+            };
+
+            // Unchanged frames just extend the previous one.
+            if previous.width == width && previous.height == height && previous.data == data {
+                return;
+            }
+        }
+
+        if self.bytes + data.len() > RECORDING_MAX_BYTES {
+            self.dropped = true;
+
+            return;
+        }
+
+        self.bytes += data.len();
+
+        self.frames.push(Frame {
+            width,
+            height,
+            data: data.to_vec(),
+            start: now,
+            duration: RECORDING_INTERVAL,
+        })
+    }
+
+    pub fn encode_gif(&self) -> Option<Vec<u8>> {
+        let frames = self.frames.iter().filter(|frame| frame.valid());
+
+        let width = frames.clone().map(|frame| frame.width).max()?;
+        let height = frames.clone().map(|frame| frame.height).max()?;
+
+        let mut result = vec![];
+
+        {
+            let mut encoder = gif::Encoder::new(
+                &mut result,
+                u16::try_from(width).ok()?,
+                u16::try_from(height).ok()?,
+                &[],
+            )
+            .ok()?;
+
+            encoder.set_repeat(gif::Repeat::Infinite).ok()?;
+
+            for frame in frames {
+                let mut data = frame.data.clone();
+
+                let mut output = gif::Frame::from_rgba_speed(
+                    frame.width as u16,
+                    frame.height as u16,
+                    &mut data,
+                    10,
+                );
+
+                // GIF delays are in hundredths of a second.
+                output.delay = (frame.duration.as_millis() / 10).clamp(1, u16::MAX as u128) as u16;
+
+                encoder.write_frame(&output).ok()?;
+            }
+        }
+
+        Some(result)
+    }
+
+    pub fn encode_png(width: u32, height: u32, data: &[u8]) -> Option<Vec<u8>> {
+        let mut result = vec![];
+
+        {
+            let mut encoder = png::Encoder::new(&mut result, width, height);
+
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let mut writer = encoder.write_header().ok()?;
+
+            writer.write_image_data(data).ok()?;
+        }
+
+        Some(result)
+    }
+
+    pub fn encode_png_frames(&self) -> Option<Vec<Vec<u8>>> {
+        self.frames
+            .iter()
+            .filter(|frame| frame.valid())
+            .map(|frame| Self::encode_png(frame.width, frame.height, &frame.data))
+            .collect()
+    }
+
+    // Numbered like name_0001.png, next to the path that was picked.
+    pub fn png_sequence(&self, path: &Path) -> Option<Vec<(PathBuf, Vec<u8>)>> {
+        let stem = path.file_stem()?.to_string_lossy();
+
+        let files = self
+            .encode_png_frames()?
+            .into_iter()
+            .enumerate()
+            .map(|(index, bytes)| {
+                let name = format!("{}_{:04}.png", stem, index + 1);
+
+                (path.with_file_name(name), bytes)
+            })
+            .collect();
+
+        Some(files)
+    }
+
+    // Headless save, returns the files that were written.
+    pub fn save(&self, path: &Path, format: RecordingFormat) -> Option<Vec<PathBuf>> {
+        let files = match format {
+            RecordingFormat::Gif => vec![(path.to_path_buf(), self.encode_gif()?)],
+            RecordingFormat::PngSequence => self.png_sequence(path)?,
+        };
+
+        for (path, bytes) in &files {
+            fs::write(path, bytes).ok()?;
+        }
+
+        Some(files.into_iter().map(|(path, _)| path).collect())
+    }
+}
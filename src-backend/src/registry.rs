@@ -0,0 +1,258 @@
+use crate::audio::AudioHandler;
+use crate::graphics::GraphicsHandler;
+use crate::keyboard::KeyboardHandler;
+use crate::lab::LabHandler;
+use crate::mmio::Peripherals;
+use crate::mouse::MouseHandler;
+use crate::scancode::ScanCodeHandler;
+use crate::screen::ScreenHandler;
+use crate::text::TextHandler;
+use crate::timer::TimerHandler;
+use crate::transmitter::TransmitterHandler;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use titan::cpu::Memory;
+
+// A peripheral living in the MMIO selector, anything implementing this can be registered.
+pub trait MmioDevice: Send + Sync {
+    // Unique per session, used to find the device's snapshot.
+    fn name(&self) -> &str;
+    // Addresses the device answers, they may not overlap another registered device.
+    fn ranges(&self) -> Vec<Range<u32>>;
+
+    fn read(&self, address: u32) -> error::Result<u8>;
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()>;
+
+    // Back to the state the device powers on with.
+    fn reset(&mut self);
+    // State for the frontend to draw, None if the device has nothing to show.
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    // Called after every slice with the instructions it ran, now is None when time is not tracked.
+    fn tick(&mut self, _instructions: u32, _now: Option<Duration>) {}
+    // Instructions until the device changes state on its own, None if it never does.
+    fn slice(&self) -> Option<u32> {
+        None
+    }
+    // Cause register IP bits the device is requesting.
+    fn pending_interrupt(&self) -> u32 {
+        0
+    }
+    // A write to address faulted, true if it was this device's doorbell and it handled it.
+    fn present(&mut self, _address: u32, _memory: &mut dyn Memory) -> bool {
+        false
+    }
+}
+
+// Devices that ship with the backend, the default session mounts all of them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceKind {
+    Keyboard,
+    Transmitter,
+    Lab,
+    Timer,
+    ScanCode,
+    Mouse,
+    Screen,
+    Text,
+    Graphics,
+    Audio,
+}
+
+impl DeviceKind {
+    pub const ALL: [DeviceKind; 10] = [
+        DeviceKind::Keyboard,
+        DeviceKind::Transmitter,
+        DeviceKind::Lab,
+        DeviceKind::Timer,
+        DeviceKind::ScanCode,
+        DeviceKind::Mouse,
+        DeviceKind::Screen,
+        DeviceKind::Text,
+        DeviceKind::Graphics,
+        DeviceKind::Audio,
+    ];
+
+    // The handler shares its state with peripherals, so the host can still reach it.
+    pub fn create(self, peripherals: &Peripherals) -> Box<dyn MmioDevice> {
+        match self {
+            DeviceKind::Keyboard => Box::new(KeyboardHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Transmitter => Box::new(TransmitterHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Lab => Box::new(LabHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Timer => Box::new(TimerHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::ScanCode => Box::new(ScanCodeHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Mouse => Box::new(MouseHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Screen => Box::new(ScreenHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Text => Box::new(TextHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Graphics => Box::new(GraphicsHandler {
+                state: peripherals.get(),
+            }),
+            DeviceKind::Audio => Box::new(AudioHandler {
+                state: peripherals.get(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct DeviceSnapshot {
+    pub name: String,
+    pub state: serde_json::Value,
+}
+
+#[derive(Default)]
+pub struct DeviceRegistry {
+    devices: Vec<Box<dyn MmioDevice>>,
+    ranges: Vec<(Range<u32>, usize)>, // cached from ranges(), with the index of the device
+}
+
+pub type DeviceRegistryBody = Arc<Mutex<DeviceRegistry>>;
+
+// Every device is mounted in the 0xFFFF selector, see configure_devices.
+pub const MMIO_START: u32 = 0xFFFF0000;
+
+fn overlaps(a: &Range<u32>, b: &Range<u32>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+impl DeviceRegistry {
+    pub fn new() -> DeviceRegistry {
+        DeviceRegistry::default()
+    }
+
+    // Err if the name is taken, the device is outside the MMIO selector
+    // or it overlaps one that is already registered.
+    pub fn register(&mut self, device: Box<dyn MmioDevice>) -> Result<(), String> {
+        if self
+            .devices
+            .iter()
+            .any(|other| other.name() == device.name())
+        {
+            return Err(format!("Device {} is already registered.", device.name()));
+        }
+
+        let ranges = device.ranges();
+
+        if let Some(range) = ranges.iter().find(|range| range.start < MMIO_START) {
+            return Err(format!(
+                "Device {} claims 0x{:08x}, devices live at 0x{:08x} and up.",
+                device.name(),
+                range.start,
+                MMIO_START
+            ));
+        }
+
+        for (range, index) in &self.ranges {
+            if ranges.iter().any(|mine| overlaps(range, mine)) {
+                return Err(format!(
+                    "Device {} overlaps the addresses of {}.",
+                    device.name(),
+                    self.devices[*index].name()
+                ));
+            }
+        }
+
+        let index = self.devices.len();
+
+        self.ranges
+            .extend(ranges.into_iter().map(|range| (range, index)));
+        self.devices.push(device);
+
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.devices
+            .iter()
+            .map(|device| device.name().to_string())
+            .collect()
+    }
+
+    pub fn reset(&mut self) {
+        for device in &mut self.devices {
+            device.reset()
+        }
+    }
+
+    pub fn tick(&mut self, instructions: u32, now: Option<Duration>) {
+        for device in &mut self.devices {
+            device.tick(instructions, now)
+        }
+    }
+
+    // Largest batch that will not step over a device changing state.
+    pub fn slice(&self, count: u32) -> u32 {
+        self.devices
+            .iter()
+            .filter_map(|device| device.slice())
+            .fold(count, |count, left| left.clamp(1, count))
+    }
+
+    pub fn pending_interrupts(&self) -> u32 {
+        self.devices
+            .iter()
+            .fold(0, |pending, device| pending | device.pending_interrupt())
+    }
+
+    pub fn present(&mut self, address: u32, memory: &mut dyn Memory) -> bool {
+        self.devices
+            .iter_mut()
+            .any(|device| device.present(address, memory))
+    }
+
+    pub fn snapshots(&self) -> Vec<DeviceSnapshot> {
+        self.devices
+            .iter()
+            .filter_map(|device| {
+                Some(DeviceSnapshot {
+                    name: device.name().to_string(),
+                    state: device.snapshot()?,
+                })
+            })
+            .collect()
+    }
+
+    fn position(&self, address: u32) -> Option<usize> {
+        self.ranges
+            .iter()
+            .find(|(range, _)| range.contains(&address))
+            .map(|(_, index)| *index)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        match self.position(address) {
+            Some(index) => self.devices[index].read(address),
+            None => Err(MemoryUnmapped(address)),
+        }
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        match self.position(address) {
+            Some(index) => self.devices[index].write(address, value),
+            None => Err(MemoryUnmapped(address)),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use crate::mmio::read_register_byte;
+use crate::registry::MmioDevice;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+// Scan code keyboard, reports every key (not just ASCII) using USB HID usage ids.
+pub const SCANCODE_ADDRESS: u32 = 0xFFFF0100;
+// One byte per key code, 1 while the key is held down.
+pub const SCANCODE_HOLDING: u32 = 0xFFFF0200;
+
+// Register offsets from SCANCODE_ADDRESS.
+// Read: bits 0 to 7 hold the number of queued events, bit 31 is set if an event was dropped.
+// Any write clears the overflow bit.
+const SCANCODE_STATUS: u32 = 0x00;
+// Read: reading the lowest byte moves the next event into the event registers
+// and returns its key code, or 0 if there were no events.
+const SCANCODE_EVENT_CODE: u32 = 0x04;
+// Read: bit 0 is set for a key-up event, bits 8 to 15 hold the modifiers at the time of the event.
+const SCANCODE_EVENT_FLAGS: u32 = 0x08;
+// Read: milliseconds since the unix epoch when the event happened, split like syscall 30.
+const SCANCODE_EVENT_TIME_LOW: u32 = 0x0C;
+const SCANCODE_EVENT_TIME_HIGH: u32 = 0x10;
+// Read: modifier keys currently held, see MODIFIER_*.
+const SCANCODE_MODIFIERS: u32 = 0x14;
+const SCANCODE_SIZE: u32 = 0x18;
+
+pub const SCANCODE_QUEUE_SIZE: usize = 32;
+
+const EVENT_UP: u32 = 1 << 0;
+const STATUS_OVERFLOW: u32 = 1 << 31;
+
+// Modifier bits, in the same order as the HID modifier byte (key code 0xE0 + bit).
+pub const MODIFIER_LEFT_CONTROL: u8 = 1 << 0;
+pub const MODIFIER_LEFT_SHIFT: u8 = 1 << 1;
+pub const MODIFIER_LEFT_ALT: u8 = 1 << 2;
+pub const MODIFIER_LEFT_META: u8 = 1 << 3;
+pub const MODIFIER_RIGHT_CONTROL: u8 = 1 << 4;
+pub const MODIFIER_RIGHT_SHIFT: u8 = 1 << 5;
+pub const MODIFIER_RIGHT_ALT: u8 = 1 << 6;
+pub const MODIFIER_RIGHT_META: u8 = 1 << 7;
+
+const MODIFIER_START: u8 = 0xE0;
+
+// Key codes for named keys, matching KeyboardEvent.code names from the frontend.
+// Letters (KeyA = 0x04 to KeyZ = 0x1D), digits (Digit1 = 0x1E to Digit0 = 0x27)
+// and function keys (F1 = 0x3A to F12 = 0x45) are computed in key_code.
+const KEY_CODES: [(&str, u8); 57] = [
+    ("Enter", 0x28),
+    ("Escape", 0x29),
+    ("Backspace", 0x2A),
+    ("Tab", 0x2B),
+    ("Space", 0x2C),
+    ("Minus", 0x2D),
+    ("Equal", 0x2E),
+    ("BracketLeft", 0x2F),
+    ("BracketRight", 0x30),
+    ("Backslash", 0x31),
+    ("Semicolon", 0x33),
+    ("Quote", 0x34),
+    ("Backquote", 0x35),
+    ("Comma", 0x36),
+    ("Period", 0x37),
+    ("Slash", 0x38),
+    ("CapsLock", 0x39),
+    ("PrintScreen", 0x46),
+    ("ScrollLock", 0x47),
+    ("Pause", 0x48),
+    ("Insert", 0x49),
+    ("Home", 0x4A),
+    ("PageUp", 0x4B),
+    ("Delete", 0x4C),
+    ("End", 0x4D),
+    ("PageDown", 0x4E),
+    ("ArrowRight", 0x4F),
+    ("ArrowLeft", 0x50),
+    ("ArrowDown", 0x51),
+    ("ArrowUp", 0x52),
+    ("NumLock", 0x53),
+    ("NumpadDivide", 0x54),
+    ("NumpadMultiply", 0x55),
+    ("NumpadSubtract", 0x56),
+    ("NumpadAdd", 0x57),
+    ("NumpadEnter", 0x58),
+    ("Numpad1", 0x59),
+    ("Numpad2", 0x5A),
+    ("Numpad3", 0x5B),
+    ("Numpad4", 0x5C),
+    ("Numpad5", 0x5D),
+    ("Numpad6", 0x5E),
+    ("Numpad7", 0x5F),
+    ("Numpad8", 0x60),
+    ("Numpad9", 0x61),
+    ("Numpad0", 0x62),
+    ("NumpadDecimal", 0x63),
+    ("IntlBackslash", 0x64),
+    ("ContextMenu", 0x65),
+    ("ControlLeft", 0xE0),
+    ("ShiftLeft", 0xE1),
+    ("AltLeft", 0xE2),
+    ("MetaLeft", 0xE3),
+    ("ControlRight", 0xE4),
+    ("ShiftRight", 0xE5),
+    ("AltRight", 0xE6),
+    ("MetaRight", 0xE7),
+];
+
+pub fn key_code(name: &str) -> Option<u8> {
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    if let Some(letter) = name.strip_prefix("Key").and_then(single) {
+        if letter.is_ascii_uppercase() {
+            return Some(0x04 + (letter as u8 - b'A'));
+        }
+    }
+
+    if let Some(digit) = name.strip_prefix("Digit").and_then(single) {
+        return match digit {
+            '0' => Some(0x27),
+            '1'..='9' => Some(0x1E + (digit as u8 - b'1')),
+            _ => None,
+        };
+    }
+
+    if let Some(number) = name.strip_prefix('F').and_then(|x| x.parse::<u8>().ok()) {
+        if (1..=12).contains(&number) {
+            return Some(0x3A + number - 1);
+        }
+    }
+
+    KEY_CODES
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, code)| *code)
+}
+
+#[derive(Copy, Clone)]
+struct KeyEvent {
+    code: u8,
+    up: bool,
+    modifiers: u8,
+    time: u64,
+}
+
+pub struct ScanCodeState {
+    events: VecDeque<KeyEvent>,
+    current: Option<KeyEvent>,
+    overflow: bool,
+    modifiers: u8,
+    holding: [bool; 256],
+}
+
+pub struct ScanCodeHandler {
+    pub state: Arc<Mutex<ScanCodeState>>,
+}
+
+impl Default for ScanCodeState {
+    fn default() -> ScanCodeState {
+        ScanCodeState::new()
+    }
+}
+
+impl ScanCodeState {
+    pub fn new() -> ScanCodeState {
+        ScanCodeState {
+            events: VecDeque::with_capacity(SCANCODE_QUEUE_SIZE),
+            current: None,
+            overflow: false,
+            modifiers: 0,
+            holding: [false; 256],
+        }
+    }
+
+    // Time is in milliseconds since the unix epoch.
+    pub fn push_key(&mut self, code: u8, up: bool, time: u64) {
+        if code >= MODIFIER_START {
+            let bit = 1u8 << (code - MODIFIER_START);
+
+            if up {
+                self.modifiers &= !bit
+            } else {
+                self.modifiers |= bit
+            }
+        }
+
+        // Held keys repeat on most platforms, only the first press is an event.
+        let repeat = !up && self.holding[code as usize];
+
+        self.holding[code as usize] = !up;
+
+        if repeat {
+            return;
+        }
+
+        if self.events.len() < SCANCODE_QUEUE_SIZE {
+            self.events.push_back(KeyEvent {
+                code,
+                up,
+                modifiers: self.modifiers,
+                time,
+            })
+        } else {
+            self.overflow = true
+        }
+    }
+
+    fn status(&self) -> u32 {
+        let overflow = if self.overflow { STATUS_OVERFLOW } else { 0 };
+
+        overflow | self.events.len() as u32
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        let current = self.current.as_ref();
+
+        match offset {
+            SCANCODE_STATUS => self.status(),
+            SCANCODE_EVENT_CODE => current.map(|event| event.code as u32).unwrap_or(0),
+            SCANCODE_EVENT_FLAGS => current
+                .map(|event| {
+                    let up = if event.up { EVENT_UP } else { 0 };
+
+                    up | ((event.modifiers as u32) << 8)
+                })
+                .unwrap_or(0),
+            SCANCODE_EVENT_TIME_LOW => current
+                .map(|event| (event.time & 0xFFFFFFFF) as u32)
+                .unwrap_or(0),
+            SCANCODE_EVENT_TIME_HIGH => current
+                .map(|event| event.time.wrapping_shr(32) as u32)
+                .unwrap_or(0),
+            SCANCODE_MODIFIERS => self.modifiers as u32,
+            _ => 0,
+        }
+    }
+}
+
+impl ScanCodeHandler {
+    pub fn new() -> ScanCodeHandler {
+        ScanCodeHandler {
+            state: Arc::new(Mutex::new(ScanCodeState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (SCANCODE_ADDRESS..SCANCODE_ADDRESS + SCANCODE_SIZE).contains(&address)
+            || (SCANCODE_HOLDING..SCANCODE_HOLDING + 256).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let mut state = self.state.lock().unwrap();
+
+        if address >= SCANCODE_HOLDING {
+            return match state.holding.get((address - SCANCODE_HOLDING) as usize) {
+                Some(held) => Ok(*held as u8),
+                None => Err(MemoryUnmapped(address)),
+            };
+        }
+
+        let offset = address.wrapping_sub(SCANCODE_ADDRESS);
+
+        if offset >= SCANCODE_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        if offset == SCANCODE_EVENT_CODE {
+            state.current = state.events.pop_front();
+        }
+
+        Ok(read_register_byte(state.read_register(offset & !3), offset))
+    }
+
+    pub fn write(&mut self, address: u32, _: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(SCANCODE_ADDRESS);
+
+        match offset {
+            SCANCODE_STATUS..SCANCODE_EVENT_CODE => {
+                self.state.lock().unwrap().overflow = false;
+
+                Ok(())
+            }
+            _ if Self::contains(address) => Ok(()),
+            _ => Err(MemoryUnmapped(address)),
+        }
+    }
+}
+
+impl MmioDevice for ScanCodeHandler {
+    fn name(&self) -> &str {
+        "scancode"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![
+            SCANCODE_ADDRESS..SCANCODE_ADDRESS + SCANCODE_SIZE,
+            SCANCODE_HOLDING..SCANCODE_HOLDING + 256,
+        ]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = ScanCodeState::new()
+    }
+}
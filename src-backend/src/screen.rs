@@ -0,0 +1,316 @@
+use crate::display::{read_display, DisplayFrame, FlushDisplayState, PixelFormat};
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::{MmioDevice, MMIO_START};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use titan::cpu::Memory;
+
+// Display control block, lets the program configure the bitmap display itself.
+pub const SCREEN_ADDRESS: u32 = 0xFFFF0400;
+
+// Register offsets from SCREEN_ADDRESS, all read/write unless noted.
+// Bit 0 hands the display over to the program, the host configuration is used while it is clear.
+const SCREEN_CONTROL: u32 = 0x00;
+// Resolution in display pixels, each at most SCREEN_MAX_SIZE.
+const SCREEN_WIDTH: u32 = 0x04;
+const SCREEN_HEIGHT: u32 = 0x08;
+// Address of the first pixel.
+const SCREEN_BASE: u32 = 0x0C;
+// 0 rgb888, 1 argb8888, 2 rgb565, 3 palette8, 4 grayscale8, 5 monochrome1.
+const SCREEN_FORMAT: u32 = 0x10;
+// Address of the 256 word palette, only used by the palette8 format.
+const SCREEN_PALETTE: u32 = 0x14;
+// Host pixels per display pixel, 0 leaves the unit size to the host.
+const SCREEN_SCALE: u32 = 0x18;
+// Write: writing the lowest byte presents the frame currently in memory, it is captured before the next instruction.
+// Read: number of frames presented so far.
+const SCREEN_PRESENT: u32 = 0x1C;
+// Address of the back buffer, the program draws here while the front buffer is shown.
+const SCREEN_BACK: u32 = 0x20;
+// Write: writing the lowest byte swaps SCREEN_BASE and SCREEN_BACK, then presents the new front buffer.
+// Read: 0 or 1, the page currently in front.
+const SCREEN_FLIP: u32 = 0x24;
+const SCREEN_SIZE: u32 = 0x28;
+
+pub const SCREEN_ENABLE: u32 = 1 << 0;
+
+pub const SCREEN_MAX_SIZE: u32 = 1024;
+
+pub struct ScreenState {
+    control: u32,
+    width: u32,
+    height: u32,
+    base: u32,
+    format: u32,
+    palette: u32,
+    scale: u32,
+    back: u32,
+    page: u32,
+    frames: u32,
+    present_requested: bool,
+    retrying: bool, // the doorbell write faulted for a capture and runs again, see present
+    presented: Option<DisplayFrame>,
+}
+
+pub struct ScreenHandler {
+    pub state: Arc<Mutex<ScreenState>>,
+}
+
+impl Default for ScreenState {
+    fn default() -> ScreenState {
+        ScreenState::new()
+    }
+}
+
+impl ScreenState {
+    pub fn new() -> ScreenState {
+        let display = FlushDisplayState::default();
+
+        ScreenState {
+            control: 0,
+            width: display.width,
+            height: display.height,
+            base: display.address,
+            format: 0,
+            palette: 0,
+            scale: 0,
+            back: display.address,
+            page: 0,
+            frames: 0,
+            present_requested: false,
+            retrying: false,
+            presented: None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.control & SCREEN_ENABLE != 0
+    }
+
+    fn pixel_format(&self) -> Option<PixelFormat> {
+        PixelFormat::from_code(self.format, self.palette)
+    }
+
+    fn scale(&self) -> Option<u32> {
+        if self.scale == 0 {
+            None
+        } else {
+            Some(self.scale)
+        }
+    }
+
+    fn valid(&self) -> bool {
+        let size = 1..=SCREEN_MAX_SIZE;
+
+        size.contains(&self.width) && size.contains(&self.height) && self.pixel_format().is_some()
+    }
+
+    // Copies the program's configuration into the display, returns false if the host is in charge.
+    pub fn configure(&self, display: &mut FlushDisplayState) -> bool {
+        if !self.enabled() || !self.valid() {
+            return false;
+        }
+
+        display.address = self.base;
+        display.register = None;
+        display.width = self.width;
+        display.height = self.height;
+        display.format = self.pixel_format().unwrap_or_default();
+        display.scale = self.scale();
+
+        true
+    }
+
+    fn read_memory<Mem: Memory + ?Sized>(&self, memory: &mut Mem) -> Option<DisplayFrame> {
+        if !self.enabled() || !self.valid() {
+            return None;
+        }
+
+        let data = read_display(
+            self.base,
+            self.width,
+            self.height,
+            self.pixel_format()?,
+            memory,
+        )?;
+
+        Some(DisplayFrame {
+            width: self.width,
+            height: self.height,
+            scale: self.scale(),
+            data,
+        })
+    }
+
+    // Reads the frame the program is currently asking for, None if the host is in charge.
+    pub fn read_display<Mem: Memory>(&self, memory: &mut Mem) -> Option<DisplayFrame> {
+        // Once the program presents frames, only presented frames are shown.
+        match &self.presented {
+            Some(frame) if self.enabled() => Some(frame.clone()),
+            _ => self.read_memory(memory),
+        }
+    }
+
+    // Captures the frame requested by the doorbell, returns false if nothing was requested.
+    // The doorbell write faults so this runs before any other instruction can touch the frame,
+    // the write is let through once when it runs again.
+    pub fn present(&mut self, memory: &mut dyn Memory) -> bool {
+        if !self.present_requested {
+            return false;
+        }
+
+        // Devices stay locked while presenting, a frame mapped over them would never finish reading.
+        let mapped = self.base >= MMIO_START || self.palette >= MMIO_START;
+
+        self.present_requested = false;
+        self.presented = if mapped {
+            None
+        } else {
+            self.read_memory(memory)
+        };
+        self.retrying = true;
+
+        true
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            SCREEN_CONTROL => self.control,
+            SCREEN_WIDTH => self.width,
+            SCREEN_HEIGHT => self.height,
+            SCREEN_BASE => self.base,
+            SCREEN_FORMAT => self.format,
+            SCREEN_PALETTE => self.palette,
+            SCREEN_SCALE => self.scale,
+            SCREEN_PRESENT => self.frames,
+            SCREEN_BACK => self.back,
+            SCREEN_FLIP => self.page,
+            _ => 0,
+        }
+    }
+
+    // True for the write that runs again after a capture, the doorbell already rang for it.
+    fn take_retry(&mut self) -> bool {
+        std::mem::replace(&mut self.retrying, false)
+    }
+
+    // Returns true if the write has to fault for the frame to be captured.
+    fn request_present(&mut self) -> bool {
+        self.frames = self.frames.wrapping_add(1);
+        self.present_requested = self.enabled() && self.valid();
+
+        self.present_requested
+    }
+
+    // Returns true if the write has to fault, see present.
+    fn write_register(&mut self, offset: u32, byte: u8) -> bool {
+        let register = match offset & !3 {
+            SCREEN_CONTROL => &mut self.control,
+            SCREEN_WIDTH => &mut self.width,
+            SCREEN_HEIGHT => &mut self.height,
+            SCREEN_BASE => &mut self.base,
+            SCREEN_FORMAT => &mut self.format,
+            SCREEN_PALETTE => &mut self.palette,
+            SCREEN_SCALE => &mut self.scale,
+            SCREEN_BACK => &mut self.back,
+            // A sw writes four bytes, only ring once.
+            SCREEN_PRESENT => {
+                return offset == SCREEN_PRESENT && !self.take_retry() && self.request_present();
+            }
+            SCREEN_FLIP => {
+                if offset != SCREEN_FLIP || self.take_retry() {
+                    return false;
+                }
+
+                std::mem::swap(&mut self.base, &mut self.back);
+
+                self.page ^= 1;
+
+                return self.request_present();
+            }
+            _ => return false,
+        };
+
+        *register = write_register_byte(*register, offset, byte);
+
+        // Switching back to the host drops frames from the program,
+        // so does changing the shape, the presented frame no longer matches it.
+        let reshaped = matches!(
+            offset & !3,
+            SCREEN_WIDTH | SCREEN_HEIGHT | SCREEN_FORMAT | SCREEN_PALETTE
+        );
+
+        if !self.enabled() || reshaped {
+            self.presented = None;
+        }
+
+        false
+    }
+}
+
+impl ScreenHandler {
+    pub fn new() -> ScreenHandler {
+        ScreenHandler {
+            state: Arc::new(Mutex::new(ScreenState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (SCREEN_ADDRESS..SCREEN_ADDRESS + SCREEN_SIZE).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let offset = address.wrapping_sub(SCREEN_ADDRESS);
+
+        if offset >= SCREEN_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let state = self.state.lock().unwrap();
+
+        Ok(read_register_byte(state.read_register(offset & !3), offset))
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(SCREEN_ADDRESS);
+
+        if offset >= SCREEN_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        // Faulting stops the executor right after the doorbell, see ScreenState::present.
+        if self.state.lock().unwrap().write_register(offset, value) {
+            return Err(MemoryUnmapped(address));
+        }
+
+        Ok(())
+    }
+}
+
+impl MmioDevice for ScreenHandler {
+    fn name(&self) -> &str {
+        "screen"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![SCREEN_ADDRESS..SCREEN_ADDRESS + SCREEN_SIZE]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = ScreenState::new()
+    }
+
+    fn present(&mut self, address: u32, memory: &mut dyn Memory) -> bool {
+        Self::contains(address) && self.state.lock().unwrap().present(memory)
+    }
+}
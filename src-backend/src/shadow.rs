@@ -0,0 +1,216 @@
+use crate::effects::{Effects, HI_SHADOW, LO_SHADOW};
+use crate::layout::MemoryLayout;
+use std::collections::HashMap;
+use std::ops::Range;
+use titan::cpu::state::Registers;
+
+const PAGE_SHIFT: u32 = 12;
+const PAGE_WORDS: usize = (1 << PAGE_SHIFT) / 64;
+
+// Most a syscall buffer is marked for, a bad length should not stall the program.
+const SHADOW_BUFFER_LIMIT: u32 = 0x10000;
+
+// Registers the loader sets up, everything else starts undefined unless it is non-zero.
+const INITIAL_DEFINED: u64 = (1 << 0) | (1 << 28) | (1 << 29) | (1 << 31);
+
+// Bits for one 4 KiB page, one per byte.
+struct ShadowPage {
+    written: [u64; PAGE_WORDS],
+    defined: [u64; PAGE_WORDS], // written with a defined value
+}
+
+// Register values as the shadow needs them, before or after a step.
+#[derive(Copy, Clone)]
+pub struct ShadowRegisters {
+    pub line: [u32; 32],
+    pub hi: u32,
+    pub lo: u32,
+}
+
+impl From<&Registers> for ShadowRegisters {
+    fn from(registers: &Registers) -> ShadowRegisters {
+        ShadowRegisters {
+            line: registers.line,
+            hi: registers.hi,
+            lo: registers.lo,
+        }
+    }
+}
+
+impl ShadowRegisters {
+    fn get(&self, register: u32) -> u32 {
+        match register {
+            HI_SHADOW => self.hi,
+            LO_SHADOW => self.lo,
+            _ => self.line[register as usize],
+        }
+    }
+}
+
+pub enum ShadowRead {
+    Register(u32),
+    Memory { address: u32, size: u32 },
+}
+
+// Defined bits for registers and for the memory a program has to initialize itself:
+// the stack and the heap. Segments, globals and devices always count as defined.
+pub struct Shadow {
+    registers: Option<u64>, // filled in from the first step
+    pages: HashMap<u32, Box<ShadowPage>>,
+    tracked: Vec<Range<u32>>,
+}
+
+impl Shadow {
+    pub fn new(layout: &MemoryLayout) -> Shadow {
+        let stack_end = layout.stack_pointer.saturating_add(4);
+
+        Shadow {
+            registers: None,
+            pages: HashMap::new(),
+            tracked: vec![
+                layout.stack_bottom()..stack_end,
+                layout.heap_start..layout.heap_limit,
+            ],
+        }
+    }
+
+    fn tracks(&self, address: u32) -> bool {
+        self.tracked.iter().any(|range| range.contains(&address))
+    }
+
+    fn registers(&mut self, registers: &ShadowRegisters) -> &mut u64 {
+        self.registers.get_or_insert_with(|| {
+            (0..32)
+                .filter(|r| registers.line[*r as usize] != 0)
+                .fold(INITIAL_DEFINED, |mask, r| mask | (1 << r))
+        })
+    }
+
+    fn byte(&self, address: u32) -> (bool, bool) {
+        if !self.tracks(address) {
+            return (true, true);
+        }
+
+        let Some(page) = self.pages.get(&(address >> PAGE_SHIFT)) else {
+            return (false, false);
+        };
+
+        let offset = (address & ((1 << PAGE_SHIFT) - 1)) as usize;
+        let mask = 1 << (offset % 64);
+
+        (
+            page.written[offset / 64] & mask != 0,
+            page.defined[offset / 64] & mask != 0,
+        )
+    }
+
+    fn mark(&mut self, address: u32, size: u32, defined: bool) {
+        for address in (0..size).map(|offset| address.wrapping_add(offset)) {
+            if !self.tracks(address) {
+                continue;
+            }
+
+            let page = self.pages.entry(address >> PAGE_SHIFT).or_insert_with(|| {
+                Box::new(ShadowPage {
+                    written: [0; PAGE_WORDS],
+                    defined: [0; PAGE_WORDS],
+                })
+            });
+
+            let offset = (address & ((1 << PAGE_SHIFT) - 1)) as usize;
+            let mask = 1 << (offset % 64);
+
+            page.written[offset / 64] |= mask;
+
+            if defined {
+                page.defined[offset / 64] |= mask
+            } else {
+                page.defined[offset / 64] &= !mask
+            }
+        }
+    }
+
+    // Reads of undefined values the instruction is about to make. Each is reported once,
+    // the value counts as defined afterwards so one mistake does not flood the console.
+    pub fn check(&mut self, effects: &Effects, registers: &ShadowRegisters) -> Vec<ShadowRead> {
+        let mut reads = vec![];
+
+        let defined = self.registers(registers);
+        let undefined = effects.reads & !*defined;
+
+        *defined |= undefined;
+
+        reads.extend(
+            (0..34)
+                .filter(|r| undefined & (1 << r) != 0)
+                .map(ShadowRead::Register),
+        );
+
+        if let Some(access) = effects.access.filter(|access| !access.store) {
+            let unwritten = (0..access.size)
+                .map(|offset| access.address.wrapping_add(offset))
+                .any(|address| !self.byte(address).0);
+
+            if unwritten {
+                self.mark(access.address, access.size, true);
+
+                reads.push(ShadowRead::Memory {
+                    address: access.address,
+                    size: access.size,
+                })
+            }
+        }
+
+        reads
+    }
+
+    // Moves defined bits along with the values the instruction just wrote.
+    pub fn commit(&mut self, effects: &Effects, before: &ShadowRegisters, after: &ShadowRegisters) {
+        // Anything that changed was written, this also covers syscalls and exception handlers.
+        let changed = (0..34)
+            .filter(|r| before.get(*r) != after.get(*r))
+            .fold(0u64, |mask, r| mask | (1 << r));
+
+        let stored = effects
+            .stored
+            .map(|r| *self.registers(before) & (1 << r) != 0);
+
+        if let Some(access) = effects.access.filter(|access| access.store) {
+            self.mark(access.address, access.size, stored.unwrap_or(true))
+        }
+
+        let loaded = effects.access.filter(|access| !access.store).map(|access| {
+            (0..access.size)
+                .map(|offset| access.address.wrapping_add(offset))
+                .all(|address| self.byte(address).1)
+        });
+
+        let defined = self.registers(before);
+
+        *defined |= changed | effects.writes;
+
+        // Saving an undefined $s register and restoring it later should not count as a read.
+        if let (Some(register), Some(loaded)) = (effects.loaded, loaded) {
+            if !loaded {
+                *defined &= !(1 << register)
+            }
+        }
+
+        if let Some((destination, source)) = effects.copy {
+            if *defined & (1 << source) == 0 {
+                *defined &= !(1 << destination)
+            }
+        }
+
+        *defined |= 1;
+
+        // Syscalls that fill a buffer.
+        match effects.syscall {
+            Some(8) => self.mark(after.line[4], after.line[5].min(SHADOW_BUFFER_LIMIT), true),
+            Some(14) if (after.line[2] as i32) > 0 => {
+                self.mark(after.line[5], after.line[2].min(SHADOW_BUFFER_LIMIT), true)
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::effects::{transfers, Effects};
+use crate::layout::MemoryLayout;
+use titan::cpu::{Memory, State};
+
+// Within this many bytes of the floor the executor steps one instruction at a time,
+// so a fault can name the instruction that moved $sp.
+pub const STACK_GUARD_MARGIN: u32 = 256;
+// Within this many bytes every instruction that writes $sp runs in a slice of its own,
+// a large frame can jump past the margin in one go.
+pub const STACK_GUARD_REACH: u32 = 0x10000;
+
+const SP_REG: u32 = 29;
+
+// Instructions that can run before the next one that may write $sp, at least 1.
+// Slices also end after a branch or jump since the path past it is not known.
+pub fn writer_slice<Mem: Memory>(state: &State<Mem>, count: u32) -> u32 {
+    let pc = state.registers.pc;
+
+    for index in 0..count {
+        let Ok(instruction) = state.memory.get_u32(pc.wrapping_add(index * 4)) else {
+            return index.max(1);
+        };
+
+        let effects = Effects::decode(instruction, &state.registers);
+
+        // A write at pc runs alone, so check_stack can name it.
+        if effects.writes & (1 << SP_REG) != 0 {
+            return index.max(1);
+        }
+
+        if transfers(instruction) {
+            return index + 1;
+        }
+    }
+
+    count
+}
+
+// Watches $sp for dropping out of the stack region or into the heap.
+pub struct StackGuard {
+    top: u32,      // initial $sp, anything above is not a stack pointer
+    bottom: u32,   // lowest address of the stack region
+    tripped: bool, // reported, stays quiet until $sp comes back
+}
+
+impl StackGuard {
+    pub fn new(layout: &MemoryLayout) -> StackGuard {
+        StackGuard {
+            top: layout.stack_pointer,
+            bottom: layout.stack_bottom(),
+            tripped: false,
+        }
+    }
+
+    // Lowest valid $sp, the heap ends at the break.
+    pub fn floor(&self, heap_break: u32) -> u32 {
+        self.bottom.max(heap_break)
+    }
+
+    pub fn near(&self, stack_pointer: u32, heap_break: u32) -> bool {
+        !self.tripped
+            && stack_pointer <= self.top
+            && stack_pointer.saturating_sub(self.floor(heap_break)) < STACK_GUARD_MARGIN
+    }
+
+    // Close enough that a single frame could cross the floor, see writer_slice.
+    pub fn within_reach(&self, stack_pointer: u32, heap_break: u32) -> bool {
+        !self.tripped
+            && stack_pointer <= self.top
+            && stack_pointer.saturating_sub(self.floor(heap_break)) < STACK_GUARD_REACH
+    }
+
+    // Some with a message the first time $sp drops below the floor.
+    // Culprit is the instruction that moved $sp when it is known, otherwise pc is where it was noticed.
+    pub fn check(
+        &mut self,
+        stack_pointer: u32,
+        heap_break: u32,
+        culprit: Option<u32>,
+        pc: u32,
+    ) -> Option<String> {
+        let floor = self.floor(heap_break);
+
+        if stack_pointer >= floor || stack_pointer > self.top {
+            self.tripped = false;
+
+            return None;
+        }
+
+        if self.tripped {
+            return None;
+        }
+
+        self.tripped = true;
+
+        let subject = match culprit {
+            Some(culprit) => format!("the instruction at 0x{:08x}", culprit),
+            None => format!("an instruction before 0x{:08x}", pc),
+        };
+
+        // The heap can only grow up to the bottom of the stack, so a break that got there
+        // is what $sp ran into.
+        Some(if stack_pointer < heap_break {
+            format!(
+                "Stack collided with the heap: {} moved $sp to 0x{:08x}, \
+                below the program break at 0x{:08x}. \
+                Pushing now overwrites memory returned by sbrk.",
+                subject, stack_pointer, heap_break
+            )
+        } else {
+            format!(
+                "Stack overflow: {} moved $sp to 0x{:08x}, \
+                below the bottom of the stack at 0x{:08x} ({} bytes). \
+                Deep or infinite recursion is the usual cause.",
+                subject,
+                stack_pointer,
+                self.bottom,
+                self.top.wrapping_add(4).wrapping_sub(self.bottom)
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build::assemble_text;
+    use crate::golden::{golden_device, run_to_point, NoTime};
+    use crate::layout::LayoutPreset;
+    use futures::executor::block_on;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    // Grows the heap right up to the stack, then recurses until $sp crosses the break.
+    const COLLIDE: &str = "
+        li $v0, 9
+        li $a0, 0
+        syscall
+        li $t0, 0x7FF00000
+        subu $a0, $t0, $v0
+        li $v0, 9
+        syscall
+    recurse:
+        addi $sp, $sp, -16
+        sw $ra, 0($sp)
+        jal recurse
+    ";
+
+    #[test]
+    fn recursing_into_the_heap_reports_a_collision() {
+        let binary = assemble_text(COLLIDE, None).unwrap_or_else(|_| panic!("Failed to assemble"));
+        let layout = LayoutPreset::Saturn.layout();
+
+        assert_eq!(layout.heap_limit, layout.stack_bottom());
+
+        let device = golden_device(binary, &layout, Arc::new(NoTime)).unwrap();
+        let message = block_on(run_to_point(&device, None, HashMap::new())).unwrap_err();
+
+        assert!(
+            message.starts_with("Stack collided with the heap"),
+            "{}",
+            message
+        );
+    }
+}
@@ -1,8 +1,18 @@
 use crate::channels::ByteChannel;
 use crate::channels::ByteChannelConsumption::{ConsumeAndContinue, ConsumeAndStop, IgnoreAndStop};
+use crate::checker::{CheckMode, Checker, Inspection};
+use crate::display::{display_peripherals, FlushDisplays};
+use crate::exception::{
+    deliver_interrupts, handle_exception, handle_syscall_exception, Coprocessor0,
+};
+use crate::heap::ProgramBreak;
+use crate::layout::MemoryLayout;
+use crate::mmio::Peripherals;
+use crate::stack::{writer_slice, StackGuard};
 use crate::syscall::SyscallResult::{
-    Aborted, Completed, Exception, Failure, Terminated, Unimplemented, Unknown,
+    Aborted, Completed, Exception, Failure, Flagged, Terminated, Unimplemented, Unknown,
 };
+use crate::transmitter::TransmitterState;
 use async_trait::async_trait;
 use futures::channel::oneshot;
 use futures::future::FusedFuture;
@@ -12,21 +22,40 @@ use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaCha8Rng;
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::future::Future;
+use std::future::{poll_fn, Future};
 use std::io::{Read, Write};
 use std::path::PathBuf;
 use std::pin::{pin, Pin};
 use std::sync::{Arc, Mutex};
+use std::task::Poll;
 use std::time::Duration;
 use titan::cpu::error::Error;
-use titan::cpu::error::Error::{CpuSyscall, CpuTrap};
+use titan::cpu::error::Error::{CpuSyscall, CpuTrap, MemoryUnmapped};
 use titan::cpu::state::Registers;
 use titan::cpu::Memory;
 use titan::execution::executor::DebugFrame;
+use titan::execution::executor::ExecutorMode;
 use titan::execution::executor::ExecutorMode::Invalid;
 use titan::execution::trackers::Tracker;
 use titan::execution::Executor;
 
+// Pending once, so the executor polls other tasks before coming back.
+async fn yield_now() {
+    let mut yielded = false;
+
+    poll_fn(|context| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            context.waker().wake_by_ref();
+
+            Poll::Pending
+        }
+    })
+    .await
+}
+
 pub struct MidiRequest {
     pub pitch: u32,      // 0 - 127
     pub duration: u32,   // in ms
@@ -43,6 +72,7 @@ pub enum SyscallResult {
     Unimplemented(u32), // User executed a recognized syscall that is not implemented yet.
     Unknown(u32),       // User executed a totally unknown syscall.
     Exception(Error),   // Some Memory/CPU error should be reported.
+    Flagged(String),    // A runtime check paused before an instruction, resuming runs it.
 }
 
 pub trait ConsoleHandler {
@@ -72,8 +102,14 @@ pub struct SyscallState {
     pub cancel_token: CancelToken,
     pub input_buffer: Arc<ByteChannel>,
     pub sync_wake: Option<oneshot::Sender<()>>,
+    pub refresh_wake: Option<oneshot::Sender<()>>,
+    pub cop0: Coprocessor0,
     current_directory: Option<String>, // for filesystem requests
-    heap_start: u32,
+    pub heap: ProgramBreak,
+    pub layout: MemoryLayout,
+    pub stack_low_water: u32, // lowest $sp seen between batches
+    pub stack: StackGuard,
+    pub checker: Option<Checker>,
     console: Box<dyn ConsoleHandler + Send + Sync>,
     midi: Box<dyn MidiHandler + Send + Sync>,
     time: Arc<dyn TimeHandler + Send + Sync>,
@@ -88,12 +124,20 @@ impl SyscallState {
         midi: Box<dyn MidiHandler + Send + Sync>,
         time: Arc<dyn TimeHandler + Send + Sync>,
         current_directory: Option<String>,
+        layout: &MemoryLayout,
+        checker: Option<Checker>,
     ) -> SyscallState {
         SyscallState {
             cancel_token: CancelToken::None,
             input_buffer: Arc::new(ByteChannel::default()),
             sync_wake: None,
-            heap_start: 0x20000000,
+            refresh_wake: None,
+            cop0: Coprocessor0::default(),
+            heap: ProgramBreak::new(layout),
+            layout: *layout,
+            stack_low_water: layout.stack_pointer,
+            stack: StackGuard::new(layout),
+            checker,
             current_directory,
             console,
             midi,
@@ -104,6 +148,17 @@ impl SyscallState {
         }
     }
 
+    pub fn now(&self) -> Option<Duration> {
+        self.time.time()
+    }
+
+    // Values outside of the stack, like $sp borrowed as a scratch register, are ignored.
+    pub fn sample_stack(&mut self, stack_pointer: u32) {
+        if stack_pointer > self.heap.current() && stack_pointer <= self.layout.stack_pointer {
+            self.stack_low_water = self.stack_low_water.min(stack_pointer)
+        }
+    }
+
     pub fn clear_cancelled(&mut self) {
         self.cancel_token = CancelToken::None
     }
@@ -131,6 +186,8 @@ impl SyscallState {
 
 pub struct SyscallDelegate {
     pub state: Arc<Mutex<SyscallState>>,
+    pub peripherals: Peripherals,
+    pub displays: FlushDisplays, // flushed periodically while recording
 }
 
 fn reg<Mem: Memory, Track: Tracker<Mem>>(debugger: &Executor<Mem, Track>, index: usize) -> u32 {
@@ -138,6 +195,7 @@ fn reg<Mem: Memory, Track: Tracker<Mem>>(debugger: &Executor<Mem, Track>, index:
 }
 
 const V0_REG: usize = 2;
+const SP_REG: usize = 29;
 const A0_REG: usize = 4;
 const A1_REG: usize = 5;
 const A2_REG: usize = 6;
@@ -158,9 +216,168 @@ fn midi_request(registers: &Registers) -> MidiRequest {
 
 const PRINT_BUFFER_TIME: Duration = Duration::from_millis(5);
 
+// Longest wait for a display refresh, roughly 60 Hz so runs without a frontend keep moving.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(16);
+
+// Devices are updated (and interrupts delivered) at least this often while running.
+const INTERRUPT_SLICE: u32 = 1000;
+
 impl SyscallDelegate {
-    pub fn new(state: Arc<Mutex<SyscallState>>) -> SyscallDelegate {
-        SyscallDelegate { state }
+    pub fn new(
+        state: Arc<Mutex<SyscallState>>,
+        peripherals: Peripherals,
+        displays: FlushDisplays,
+    ) -> SyscallDelegate {
+        SyscallDelegate {
+            state,
+            peripherals,
+            displays,
+        }
+    }
+
+    fn record<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        now: Option<Duration>,
+    ) {
+        for (id, display) in &self.displays {
+            let mut display = display.lock().unwrap();
+            let peripherals = display_peripherals(id, &self.peripherals);
+
+            if display.recording_due(now) {
+                debugger.with_state(|s| display.flush(s, peripherals, now))
+            }
+        }
+    }
+
+    // Advances devices by the instructions just executed and takes any pending interrupt.
+    fn tick<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        instructions: u32,
+    ) {
+        let now = self.state.lock().unwrap().time.time();
+
+        self.peripherals.tick(instructions, now);
+
+        self.flush_transmitter();
+        self.record(debugger, now);
+
+        let pending = self.peripherals.pending_interrupts();
+        let mut syscall = self.state.lock().unwrap();
+
+        debugger.with_state(|s| {
+            syscall.sample_stack(s.registers.line[SP_REG]);
+
+            deliver_interrupts(&mut syscall.cop0, pending, s)
+        });
+    }
+
+    // Slices shrink to a single instruction while checks are on or $sp is close to the floor.
+    // Further up, but still within reach, they stop at every instruction that writes $sp.
+    fn narrow_slice<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        count: u32,
+    ) -> u32 {
+        let syscall = self.state.lock().unwrap();
+        let stack_pointer = reg(debugger, SP_REG);
+        let heap_break = syscall.heap.current();
+
+        if syscall.checker.is_some() || syscall.stack.near(stack_pointer, heap_break) {
+            1
+        } else if syscall.stack.within_reach(stack_pointer, heap_break) {
+            debugger.with_state(|s| writer_slice(s, count))
+        } else {
+            count
+        }
+    }
+
+    // Pauses with a failure if $sp left the stack during a slice that started at start_pc.
+    fn check_stack<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        count: u32,
+        start_pc: u32,
+    ) -> Option<SyscallResult> {
+        let mut syscall = self.state.lock().unwrap();
+        let heap_break = syscall.heap.current();
+
+        let (stack_pointer, pc) =
+            debugger.with_state(|s| (s.registers.line[SP_REG], s.registers.pc));
+
+        let culprit = (count == 1).then_some(start_pc);
+        let message = syscall
+            .stack
+            .check(stack_pointer, heap_break, culprit, pc)?;
+
+        debugger.pause();
+
+        Some(Failure(message))
+    }
+
+    // Checks the instruction about to run. Err if a check wants to pause before it does,
+    // findings are marked as reported so resuming runs the instruction.
+    fn inspect<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+    ) -> Result<Option<Inspection>, SyscallResult> {
+        let mut lock = self.state.lock().unwrap();
+        let syscall = &mut *lock;
+
+        let Some(checker) = &mut syscall.checker else {
+            return Ok(None);
+        };
+
+        let Some((inspection, findings)) = debugger.with_state(|s| checker.inspect(s)) else {
+            return Ok(None);
+        };
+
+        let mut messages = vec![];
+
+        for finding in findings {
+            match finding.mode {
+                CheckMode::Pause => messages.push(finding.message),
+                _ => syscall
+                    .console
+                    .print(&format!("{}\n", finding.message), true),
+            }
+        }
+
+        if messages.is_empty() {
+            Ok(Some(inspection))
+        } else {
+            debugger.pause();
+
+            Err(Flagged(messages.join("\n")))
+        }
+    }
+
+    fn commit<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        inspection: Option<Inspection>,
+    ) {
+        let Some(inspection) = inspection else { return };
+
+        if let Some(checker) = &mut self.state.lock().unwrap().checker {
+            debugger.with_state(|s| checker.commit(&inspection, s))
+        }
+    }
+
+    // Transmitter output goes through the same console as syscall output, in the order it was written.
+    fn flush_transmitter(&self) {
+        let output = self
+            .peripherals
+            .with(|transmitter: &mut TransmitterState| transmitter.take_output());
+
+        if output.is_empty() {
+            return;
+        }
+
+        let text: String = output.into_iter().map(|byte| byte as char).collect();
+
+        self.state.lock().unwrap().console.print(&text, false);
     }
 
     async fn send_print(&self, text: &str) {
@@ -400,16 +617,21 @@ impl SyscallDelegate {
         &self,
         debugger: &Executor<Mem, Track>,
     ) -> SyscallResult {
-        let count = a0(debugger);
+        let amount = a0(debugger) as i32;
+        // The trap already moved pc past the syscall, sbrk names the syscall itself.
+        let pc = debugger.with_state(|s| s.registers.pc.wrapping_sub(4));
 
-        // Primitive Heap Alloc, assuming 0x20000000 is safe heap.
-        let mut syscall = self.state.lock().unwrap();
-        let pointer = syscall.heap_start;
-        syscall.heap_start += count;
+        // The heap is only backed by the layout's writable range, nothing is mounted here.
+        let result = self.state.lock().unwrap().heap.sbrk(amount, pc);
 
-        debugger.with_state(|s| s.registers.line[V0_REG] = pointer);
+        match result {
+            Ok(pointer) => {
+                debugger.with_state(|s| s.registers.line[V0_REG] = pointer);
 
-        Completed
+                Completed
+            }
+            Err(message) => Failure(message),
+        }
     }
 
     async fn terminate<Mem: Memory, Track: Tracker<Mem>>(
@@ -734,6 +956,24 @@ impl SyscallDelegate {
         Completed
     }
 
+    async fn wait_for_refresh<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+    ) -> SyscallResult {
+        let (sender, receiver) = oneshot::channel();
+
+        self.state.lock().unwrap().refresh_wake = Some(sender);
+
+        let time = self.state.lock().unwrap().time.clone();
+
+        select! {
+            _ = receiver.fuse() => {},
+            _ = time.sleep(REFRESH_INTERVAL).fuse() => {},
+        }
+
+        Completed
+    }
+
     async fn print_hexadecimal<Mem: Memory, Track: Tracker<Mem>>(
         &self,
         state: &Executor<Mem, Track>,
@@ -893,15 +1133,50 @@ impl SyscallDelegate {
             42 => self.wrap_cancel(self.random_int_ranged(state).fuse()).await,
             43 => self.wrap_cancel(self.random_float(state).fuse()).await,
             44 => self.wrap_cancel(self.random_double(state).fuse()).await,
+            60 => self.wrap_cancel(self.wait_for_refresh(state).fuse()).await,
             _ => Unknown(code),
         }
     }
 
+    // True if a syscall's error was handed to the program's exception handler.
+    fn enter_handler<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        error: &Error,
+    ) -> bool {
+        let mut syscall = self.state.lock().unwrap();
+
+        debugger.with_state(|s| handle_syscall_exception(&mut syscall.cop0, error, s))
+    }
+
+    // A doorbell write faults so its device can read memory before anything else runs.
+    // True if that is why the slice stopped, the write runs again as the start of the next one.
+    fn rang_doorbell<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+    ) -> bool {
+        let address = match debugger.frame().mode {
+            Invalid(MemoryUnmapped(address)) => address,
+            _ => return false,
+        };
+
+        if !debugger.with_state(|s| self.peripherals.present(s, address)) {
+            return false;
+        }
+
+        debugger.override_mode(ExecutorMode::Running);
+
+        true
+    }
+
     async fn handle_frame<Mem: Memory, Track: Tracker<Mem>>(
         &self,
         debugger: &Executor<Mem, Track>,
         frame: DebugFrame,
     ) -> (Option<DebugFrame>, Option<SyscallResult>, bool) {
+        // Anything sent before the batch stopped prints before the syscall does.
+        self.flush_transmitter();
+
         match frame.mode {
             Invalid(CpuSyscall) => {
                 // $v0
@@ -914,9 +1189,30 @@ impl SyscallDelegate {
 
                         (None, Some(result), true)
                     }
+                    // Bad pointers passed to a syscall go to the user handler like any other fault.
+                    Exception(ref error) if self.enter_handler(debugger, error) => {
+                        debugger.override_mode(ExecutorMode::Running);
+
+                        (None, None, true)
+                    }
                     _ => (Some(frame), Some(result), false),
                 }
             }
+            Invalid(ref error) => {
+                let handled = {
+                    let mut syscall = self.state.lock().unwrap();
+
+                    debugger.with_state(|s| handle_exception(&mut syscall.cop0, error, s))
+                };
+
+                if handled {
+                    debugger.override_mode(ExecutorMode::Running);
+
+                    (None, None, true)
+                } else {
+                    (Some(frame), None, false)
+                }
+            }
             _ => (Some(frame), None, false),
         }
     }
@@ -926,29 +1222,85 @@ impl SyscallDelegate {
         &self,
         debugger: &Executor<Mem, Track>,
         batch: usize,
-        should_skip_first: bool,
+        mut should_skip_first: bool,
         allow_interrupt: bool,
     ) -> Option<(DebugFrame, Option<SyscallResult>)> {
-        if !debugger
-            .run_batched(batch, should_skip_first, allow_interrupt)
-            .interrupted
-        {
-            return None; // no interruption, batch completed successfully
-        }
+        let mut remaining = batch;
+
+        while remaining > 0 {
+            let count = self
+                .peripherals
+                .slice(remaining.min(INTERRUPT_SLICE as usize) as u32);
+            let count = self.narrow_slice(debugger, count);
+            let start_pc = debugger.with_state(|s| s.registers.pc);
+
+            let inspection = match self.inspect(debugger) {
+                Ok(inspection) => inspection,
+                Err(result) => return Some((debugger.frame(), Some(result))),
+            };
 
-        let frame_in = debugger.frame();
+            let batch = debugger.run_batched(count as usize, should_skip_first, allow_interrupt);
+            let interrupted = batch.interrupted;
+            let executed = batch.instructions_executed as u32;
 
-        let (frame, result, recovered) = self.handle_frame(debugger, frame_in).await;
+            should_skip_first = false;
 
-        if let Some(frame) = frame {
-            return Some((frame, result));
-        }
+            if interrupted && self.rang_doorbell(debugger) {
+                // Only what ran before the write counts, it is inspected again with the next slice.
+                remaining -= executed as usize;
 
-        if !recovered {
-            return Some((debugger.frame(), None));
+                if executed > 0 {
+                    self.tick(debugger, executed);
+                    self.commit(debugger, inspection);
+
+                    if let Some(result) = self.check_stack(debugger, executed, start_pc) {
+                        return Some((debugger.frame(), Some(result)));
+                    }
+                }
+
+                // Its breakpoint, if any, already stopped the run before it faulted.
+                should_skip_first = true;
+
+                continue;
+            }
+
+            remaining -= count as usize;
+
+            if !interrupted {
+                self.tick(debugger, count);
+                self.commit(debugger, inspection);
+
+                if let Some(result) = self.check_stack(debugger, count, start_pc) {
+                    return Some((debugger.frame(), Some(result)));
+                }
+
+                continue;
+            }
+
+            let frame_in = debugger.frame();
+
+            let (frame, result, recovered) = self.handle_frame(debugger, frame_in).await;
+
+            if let Some(frame) = frame {
+                return Some((frame, result));
+            }
+
+            if !recovered {
+                return Some((debugger.frame(), None));
+            }
+
+            // Interrupted slices stop early, the interrupting instruction finished once handled.
+            self.tick(debugger, executed + 1);
+            self.commit(debugger, inspection);
+
+            if let Some(result) = self.check_stack(debugger, count, start_pc) {
+                return Some((debugger.frame(), Some(result)));
+            }
+
+            return None;
         }
 
-        None
+        None // no interruption, batch completed successfully
     }
 
     pub async fn run<Mem: Memory, Track: Tracker<Mem>>(
@@ -957,17 +1309,61 @@ impl SyscallDelegate {
         mut should_skip_first: bool,
     ) -> (DebugFrame, Option<SyscallResult>) {
         loop {
-            let frame = debugger.run(should_skip_first);
-            let (frame, result, recovered) = self.handle_frame(debugger, frame).await;
+            // Slices never await on their own, this lets a timeout around the run fire.
+            yield_now().await;
+
+            let count = self.peripherals.slice(INTERRUPT_SLICE);
+            let count = self.narrow_slice(debugger, count);
+            let start_pc = debugger.with_state(|s| s.registers.pc);
+
+            let inspection = match self.inspect(debugger) {
+                Ok(inspection) => inspection,
+                Err(result) => return (debugger.frame(), Some(result)),
+            };
+
+            let batch = debugger.run_batched(count as usize, should_skip_first, true);
+            let interrupted = batch.interrupted;
+            let executed = batch.instructions_executed as u32;
 
             should_skip_first = false;
 
-            if let Some(frame) = frame {
-                return (frame, result);
+            if interrupted && self.rang_doorbell(debugger) {
+                // Same as run_batch, the write runs again through the next slice.
+                if executed > 0 {
+                    self.tick(debugger, executed);
+                    self.commit(debugger, inspection);
+
+                    if let Some(result) = self.check_stack(debugger, executed, start_pc) {
+                        return (debugger.frame(), Some(result));
+                    }
+                }
+
+                should_skip_first = true;
+
+                continue;
             }
 
-            if !recovered {
-                return (debugger.frame(), None);
+            if interrupted {
+                let frame = debugger.frame();
+                let (frame, result, recovered) = self.handle_frame(debugger, frame).await;
+
+                if let Some(frame) = frame {
+                    return (frame, result);
+                }
+
+                if !recovered {
+                    return (debugger.frame(), None);
+                }
+            }
+
+            // Same accounting as run_batch, devices only advance by what ran.
+            let ran = if interrupted { executed + 1 } else { count };
+
+            self.tick(debugger, ran);
+            self.commit(debugger, inspection);
+
+            if let Some(result) = self.check_stack(debugger, count, start_pc) {
+                return (debugger.frame(), Some(result));
             }
         }
     }
@@ -0,0 +1,189 @@
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use serde::Serialize;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+// Character cell display, each cell is a character byte followed by an attribute byte.
+pub const TEXT_ADDRESS: u32 = 0xFFFF0500;
+pub const TEXT_BUFFER: u32 = 0xFFFF1000;
+
+pub const TEXT_COLUMNS: u32 = 80;
+pub const TEXT_ROWS: u32 = 25;
+
+const TEXT_BUFFER_SIZE: u32 = TEXT_COLUMNS * TEXT_ROWS * 2;
+
+// Register offsets from TEXT_ADDRESS.
+// Read only: size of the display in cells.
+const TEXT_COLUMNS_REGISTER: u32 = 0x00;
+const TEXT_ROWS_REGISTER: u32 = 0x04;
+// Bits 0 to 7 hold the cursor column, bits 8 to 15 the row, bit 31 shows the cursor.
+const TEXT_CURSOR: u32 = 0x08;
+// Write: writing the lowest byte fills every cell with a space using that attribute.
+const TEXT_CLEAR: u32 = 0x0C;
+const TEXT_SIZE: u32 = 0x10;
+
+// Attributes are VGA style, bits 0 to 3 pick the foreground and bits 4 to 7 the background
+// out of the 16 CGA colors.
+pub const TEXT_DEFAULT_ATTRIBUTE: u8 = 0x07; // light gray on black
+
+const CURSOR_VISIBLE: u32 = 1 << 31;
+
+#[derive(Clone, Serialize)]
+pub struct TextDisplay {
+    pub columns: u32,
+    pub rows: u32,
+    pub cursor: Option<(u32, u32)>, // column, row
+    pub cells: Vec<u8>,             // character, attribute pairs in row order
+}
+
+pub struct TextState {
+    cells: Vec<u8>,
+    cursor: u32,
+}
+
+pub struct TextHandler {
+    pub state: Arc<Mutex<TextState>>,
+}
+
+impl Default for TextState {
+    fn default() -> TextState {
+        TextState::new()
+    }
+}
+
+impl TextState {
+    pub fn new() -> TextState {
+        let mut state = TextState {
+            cells: vec![0; TEXT_BUFFER_SIZE as usize],
+            cursor: 0,
+        };
+
+        state.clear(TEXT_DEFAULT_ATTRIBUTE);
+
+        state
+    }
+
+    fn clear(&mut self, attribute: u8) {
+        for cell in self.cells.chunks_exact_mut(2) {
+            cell[0] = b' ';
+            cell[1] = attribute;
+        }
+    }
+
+    pub fn snapshot(&self) -> TextDisplay {
+        let cursor = if self.cursor & CURSOR_VISIBLE != 0 {
+            let column = self.cursor & 0xFF;
+            let row = (self.cursor >> 8) & 0xFF;
+
+            Some((column.min(TEXT_COLUMNS - 1), row.min(TEXT_ROWS - 1)))
+        } else {
+            None
+        };
+
+        TextDisplay {
+            columns: TEXT_COLUMNS,
+            rows: TEXT_ROWS,
+            cursor,
+            cells: self.cells.clone(),
+        }
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            TEXT_COLUMNS_REGISTER => TEXT_COLUMNS,
+            TEXT_ROWS_REGISTER => TEXT_ROWS,
+            TEXT_CURSOR => self.cursor,
+            _ => 0,
+        }
+    }
+}
+
+impl TextHandler {
+    pub fn new() -> TextHandler {
+        TextHandler {
+            state: Arc::new(Mutex::new(TextState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (TEXT_ADDRESS..TEXT_ADDRESS + TEXT_SIZE).contains(&address)
+            || (TEXT_BUFFER..TEXT_BUFFER + TEXT_BUFFER_SIZE).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let state = self.state.lock().unwrap();
+
+        if address >= TEXT_BUFFER {
+            return match state.cells.get((address - TEXT_BUFFER) as usize) {
+                Some(value) => Ok(*value),
+                None => Err(MemoryUnmapped(address)),
+            };
+        }
+
+        let offset = address.wrapping_sub(TEXT_ADDRESS);
+
+        if offset >= TEXT_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        Ok(read_register_byte(state.read_register(offset & !3), offset))
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if address >= TEXT_BUFFER {
+            return match state.cells.get_mut((address - TEXT_BUFFER) as usize) {
+                Some(cell) => {
+                    *cell = value;
+
+                    Ok(())
+                }
+                None => Err(MemoryUnmapped(address)),
+            };
+        }
+
+        let offset = address.wrapping_sub(TEXT_ADDRESS);
+
+        match offset & !3 {
+            TEXT_CURSOR => state.cursor = write_register_byte(state.cursor, offset, value),
+            TEXT_CLEAR if offset == TEXT_CLEAR => state.clear(value),
+            _ if offset < TEXT_SIZE => {}
+            _ => return Err(MemoryUnmapped(address)),
+        }
+
+        Ok(())
+    }
+}
+
+impl MmioDevice for TextHandler {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![
+            TEXT_ADDRESS..TEXT_ADDRESS + TEXT_SIZE,
+            TEXT_BUFFER..TEXT_BUFFER + TEXT_BUFFER_SIZE,
+        ]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = TextState::new()
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self.state.lock().unwrap().snapshot()).ok()
+    }
+}
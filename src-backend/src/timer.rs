@@ -0,0 +1,223 @@
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+pub const TIMER_ADDRESS: u32 = 0xFFFF0020;
+pub const TIMER_SIZE: u32 = 0x10;
+pub const TIMER_INTERRUPT: u32 = 1 << 15; // Cause IP7, like the MIPS count/compare timer
+
+// Register offsets from TIMER_ADDRESS.
+const TIMER_CONTROL: u32 = 0x0; // read/write, TIMER_* flags
+const TIMER_STATUS: u32 = 0x4; // bit 0 set when the timer fired, any write clears it
+const TIMER_PERIOD: u32 = 0x8; // instructions or milliseconds, depending on TIMER_WALL_CLOCK
+const TIMER_COUNTER: u32 = 0xC; // read only, instructions or milliseconds left
+
+pub const TIMER_ENABLE: u32 = 1 << 0;
+pub const TIMER_INTERRUPT_ENABLE: u32 = 1 << 1;
+pub const TIMER_WALL_CLOCK: u32 = 1 << 2;
+pub const TIMER_REPEAT: u32 = 1 << 3;
+
+pub struct TimerState {
+    control: u32,
+    period: u32,
+    remaining: u32,             // instruction mode
+    deadline: Option<Duration>, // wall clock mode, set on the first tick after arming
+    fired: bool,
+}
+
+pub struct TimerHandler {
+    pub state: Arc<Mutex<TimerState>>,
+}
+
+impl Default for TimerState {
+    fn default() -> TimerState {
+        TimerState::new()
+    }
+}
+
+impl TimerState {
+    pub fn new() -> TimerState {
+        TimerState {
+            control: 0,
+            period: 0,
+            remaining: 0,
+            deadline: None,
+            fired: false,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.control & TIMER_ENABLE != 0 && self.period != 0
+    }
+
+    fn wall_clock(&self) -> bool {
+        self.control & TIMER_WALL_CLOCK != 0
+    }
+
+    fn arm(&mut self) {
+        self.remaining = self.period;
+        self.deadline = None;
+    }
+
+    fn expire(&mut self) {
+        self.fired = true;
+
+        if self.control & TIMER_REPEAT == 0 {
+            self.control &= !TIMER_ENABLE;
+        }
+    }
+
+    pub fn pending(&self) -> bool {
+        self.fired && self.control & TIMER_INTERRUPT_ENABLE != 0
+    }
+
+    // Instructions until the timer fires, if it is counting instructions.
+    pub fn instructions_left(&self) -> Option<u32> {
+        if self.enabled() && !self.wall_clock() {
+            Some(self.remaining)
+        } else {
+            None
+        }
+    }
+
+    pub fn tick(&mut self, instructions: u32, now: Option<Duration>) {
+        if !self.enabled() {
+            return;
+        }
+
+        if self.wall_clock() {
+            let Some(now) = now else { return };
+
+            let period = Duration::from_millis(self.period as u64);
+
+            match self.deadline {
+                None => self.deadline = Some(now + period),
+                Some(deadline) if now >= deadline => {
+                    self.deadline = Some(now + period);
+                    self.expire()
+                }
+                Some(deadline) => {
+                    self.remaining = (deadline - now).as_millis() as u32;
+                }
+            }
+        } else if instructions >= self.remaining {
+            self.remaining = self.period;
+            self.expire()
+        } else {
+            self.remaining -= instructions
+        }
+    }
+
+    fn read_register(&self, offset: u32) -> u32 {
+        match offset {
+            TIMER_CONTROL => self.control,
+            TIMER_STATUS => self.fired as u32,
+            TIMER_PERIOD => self.period,
+            TIMER_COUNTER => self.remaining,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&mut self, offset: u32, value: u32) {
+        match offset {
+            TIMER_CONTROL => {
+                let starting = self.control & TIMER_ENABLE == 0 && value & TIMER_ENABLE != 0;
+
+                self.control = value;
+
+                if starting {
+                    self.arm()
+                }
+            }
+            TIMER_STATUS => self.fired = false,
+            TIMER_PERIOD => {
+                self.period = value;
+                self.arm()
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TimerHandler {
+    pub fn new() -> TimerHandler {
+        TimerHandler {
+            state: Arc::new(Mutex::new(TimerState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (TIMER_ADDRESS..TIMER_ADDRESS + TIMER_SIZE).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let offset = address.wrapping_sub(TIMER_ADDRESS);
+
+        if offset >= TIMER_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let state = self.state.lock().unwrap();
+
+        Ok(read_register_byte(state.read_register(offset & !3), offset))
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(TIMER_ADDRESS);
+
+        if offset >= TIMER_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        let register = offset & !3;
+        let current = state.read_register(register);
+
+        state.write_register(register, write_register_byte(current, offset, value));
+
+        Ok(())
+    }
+}
+
+impl MmioDevice for TimerHandler {
+    fn name(&self) -> &str {
+        "timer"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![TIMER_ADDRESS..TIMER_ADDRESS + TIMER_SIZE]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        *self.state.lock().unwrap() = TimerState::new()
+    }
+
+    fn tick(&mut self, instructions: u32, now: Option<Duration>) {
+        self.state.lock().unwrap().tick(instructions, now)
+    }
+
+    fn slice(&self) -> Option<u32> {
+        self.state.lock().unwrap().instructions_left()
+    }
+
+    fn pending_interrupt(&self) -> u32 {
+        if self.state.lock().unwrap().pending() {
+            TIMER_INTERRUPT
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+// Transmitter half of the MARS Keyboard and Display MMIO Simulator.
+pub const TRANSMITTER_CONTROL: u32 = 0xFFFF0008;
+pub const TRANSMITTER_DATA: u32 = 0xFFFF000C;
+// Read/write: instructions the transmitter stays busy after a write, not part of MARS.
+pub const TRANSMITTER_DELAY_REGISTER: u32 = 0xFFFF0048;
+pub const TRANSMITTER_INTERRUPT: u32 = 1 << 9; // Cause IP1, same as MARS
+
+// Instructions before the transmitter is ready again, MARS uses the same default.
+pub const TRANSMITTER_DELAY: u32 = 5;
+
+const TRANSMITTER_READY: u8 = 1 << 0;
+const TRANSMITTER_INTERRUPT_ENABLE: u8 = 1 << 1;
+
+pub struct TransmitterState {
+    output: Vec<u8>, // sent bytes, printed by the syscall delegate with the rest of the output
+    delay: u32,
+    remaining: u32,
+    interrupt_enabled: bool,
+    data: u32,
+}
+
+pub struct TransmitterHandler {
+    pub state: Arc<Mutex<TransmitterState>>,
+}
+
+impl Default for TransmitterState {
+    fn default() -> TransmitterState {
+        TransmitterState::new()
+    }
+}
+
+impl TransmitterState {
+    pub fn new() -> TransmitterState {
+        TransmitterState {
+            output: vec![],
+            delay: TRANSMITTER_DELAY,
+            remaining: 0,
+            interrupt_enabled: false,
+            data: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.output.clear();
+        self.delay = TRANSMITTER_DELAY;
+        self.remaining = 0;
+        self.interrupt_enabled = false;
+        self.data = 0;
+    }
+
+    pub fn set_delay(&mut self, delay: u32) {
+        self.delay = delay;
+        self.remaining = self.remaining.min(delay);
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    fn ready(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub fn pending(&self) -> bool {
+        self.interrupt_enabled && self.ready()
+    }
+
+    // Instructions until the transmitter is ready, if it is busy.
+    pub fn instructions_left(&self) -> Option<u32> {
+        if self.ready() {
+            None
+        } else {
+            Some(self.remaining)
+        }
+    }
+
+    pub fn tick(&mut self, instructions: u32) {
+        self.remaining = self.remaining.saturating_sub(instructions)
+    }
+
+    fn transmit(&mut self, value: u8) {
+        self.data = value as u32;
+
+        // Writes while busy are dropped, just like the real device.
+        if !self.ready() {
+            return;
+        }
+
+        self.output.push(value);
+
+        self.remaining = self.delay;
+    }
+}
+
+impl TransmitterHandler {
+    pub fn new() -> TransmitterHandler {
+        TransmitterHandler {
+            state: Arc::new(Mutex::new(TransmitterState::new())),
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (TRANSMITTER_CONTROL..TRANSMITTER_DATA + 4).contains(&address)
+            || Self::contains_delay(address)
+    }
+
+    fn contains_delay(address: u32) -> bool {
+        (TRANSMITTER_DELAY_REGISTER..TRANSMITTER_DELAY_REGISTER + 4).contains(&address)
+    }
+
+    pub fn read(&self, address: u32) -> error::Result<u8> {
+        let state = self.state.lock().unwrap();
+
+        match address {
+            TRANSMITTER_CONTROL => {
+                let ready = if state.ready() { TRANSMITTER_READY } else { 0 };
+
+                let enabled = if state.interrupt_enabled {
+                    TRANSMITTER_INTERRUPT_ENABLE
+                } else {
+                    0
+                };
+
+                Ok(ready | enabled)
+            }
+            _ if Self::contains_delay(address) => Ok(read_register_byte(state.delay, address)),
+            _ if Self::contains(address) && address >= TRANSMITTER_DATA => {
+                Ok(read_register_byte(state.data, address))
+            }
+            _ if Self::contains(address) => Ok(0),
+            _ => Err(MemoryUnmapped(address)),
+        }
+    }
+
+    pub fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        match address {
+            TRANSMITTER_CONTROL => {
+                // Ready is read only.
+                state.interrupt_enabled = value & TRANSMITTER_INTERRUPT_ENABLE != 0;
+
+                Ok(())
+            }
+            // Only the low byte of the data register is sent.
+            TRANSMITTER_DATA => {
+                state.transmit(value);
+
+                Ok(())
+            }
+            _ if Self::contains_delay(address) => {
+                let delay = write_register_byte(state.delay, address, value);
+
+                state.set_delay(delay);
+
+                Ok(())
+            }
+            _ if Self::contains(address) => Ok(()),
+            _ => Err(MemoryUnmapped(address)),
+        }
+    }
+}
+
+impl MmioDevice for TransmitterHandler {
+    fn name(&self) -> &str {
+        "transmitter"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![
+            TRANSMITTER_CONTROL..TRANSMITTER_DATA + 4,
+            TRANSMITTER_DELAY_REGISTER..TRANSMITTER_DELAY_REGISTER + 4,
+        ]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        self.read(address)
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        self.write(address, value)
+    }
+
+    fn reset(&mut self) {
+        self.state.lock().unwrap().reset()
+    }
+
+    fn tick(&mut self, instructions: u32, _now: Option<Duration>) {
+        self.state.lock().unwrap().tick(instructions)
+    }
+
+    fn slice(&self) -> Option<u32> {
+        self.state.lock().unwrap().instructions_left()
+    }
+
+    fn pending_interrupt(&self) -> u32 {
+        if self.state.lock().unwrap().pending() {
+            TRANSMITTER_INTERRUPT
+        } else {
+            0
+        }
+    }
+}
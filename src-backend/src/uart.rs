@@ -0,0 +1,199 @@
+use crate::mmio::{read_register_byte, write_register_byte};
+use crate::registry::MmioDevice;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+
+// Serial port, the other end is whatever the host bridges the port to (a TCP socket, a PTY).
+pub const UART_ADDRESS: u32 = 0xFFFF0800;
+
+// Register offsets from UART_ADDRESS.
+// Read: takes the oldest received byte, 0 if there is none. Write: sends the lowest byte.
+const UART_DATA: u32 = 0x00;
+// Read only: UART_* status flags. Any write clears UART_OVERFLOW.
+const UART_STATUS: u32 = 0x04;
+// Read/write: kept so bring-up code can program it, the bridge is never throttled.
+const UART_BAUD: u32 = 0x08;
+const UART_SIZE: u32 = 0x0C;
+
+pub const UART_RX_READY: u32 = 1 << 0;
+pub const UART_TX_READY: u32 = 1 << 1;
+// A received byte was dropped because the program was not reading fast enough.
+pub const UART_OVERFLOW: u32 = 1 << 2;
+// Something is attached to the other end.
+pub const UART_CONNECTED: u32 = 1 << 3;
+
+pub const UART_DEFAULT_BAUD: u32 = 9600;
+pub const UART_BUFFER_SIZE: usize = 4096;
+
+// Both directions of the wire. Owned by the host so a connection outlives a single run.
+#[derive(Default)]
+pub struct UartPort {
+    input: VecDeque<u8>,  // host to program
+    output: VecDeque<u8>, // program to host
+    overflow: bool,
+    link: Option<Arc<AtomicBool>>, // set by the link while something is attached to it
+}
+
+pub type UartPortBody = Arc<Mutex<UartPort>>;
+
+impl UartPort {
+    pub fn new() -> UartPort {
+        UartPort::default()
+    }
+
+    // Bytes past UART_BUFFER_SIZE are dropped and flag an overflow.
+    pub fn push_input(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            if self.input.len() < UART_BUFFER_SIZE {
+                self.input.push_back(*byte)
+            } else {
+                self.overflow = true
+            }
+        }
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.output.drain(..).collect()
+    }
+
+    // The flag belongs to the current link, a link that was replaced can no longer change it.
+    pub fn attach(&mut self, link: Option<Arc<AtomicBool>>) {
+        self.link = link
+    }
+
+    pub fn connected(&self) -> bool {
+        self.link
+            .as_ref()
+            .is_some_and(|connected| connected.load(Ordering::Relaxed))
+    }
+
+    // Anything the last program left behind should not reach the next one.
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.output.clear();
+        self.overflow = false;
+    }
+
+    fn status(&self) -> u32 {
+        let mut status = 0;
+
+        if !self.input.is_empty() {
+            status |= UART_RX_READY
+        }
+
+        if self.output.len() < UART_BUFFER_SIZE {
+            status |= UART_TX_READY
+        }
+
+        if self.overflow {
+            status |= UART_OVERFLOW
+        }
+
+        if self.connected() {
+            status |= UART_CONNECTED
+        }
+
+        status
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct UartDisplay {
+    pub baud: u32,
+    pub connected: bool,
+    pub input: usize,  // bytes waiting for the program
+    pub output: usize, // bytes waiting for the host
+}
+
+pub struct UartHandler {
+    pub port: UartPortBody,
+    baud: u32,
+}
+
+impl UartHandler {
+    pub fn new(port: UartPortBody) -> UartHandler {
+        UartHandler {
+            port,
+            baud: UART_DEFAULT_BAUD,
+        }
+    }
+
+    pub fn contains(address: u32) -> bool {
+        (UART_ADDRESS..UART_ADDRESS + UART_SIZE).contains(&address)
+    }
+}
+
+impl MmioDevice for UartHandler {
+    fn name(&self) -> &str {
+        "uart"
+    }
+
+    fn ranges(&self) -> Vec<Range<u32>> {
+        vec![UART_ADDRESS..UART_ADDRESS + UART_SIZE]
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        let offset = address.wrapping_sub(UART_ADDRESS);
+
+        if offset >= UART_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let mut port = self.port.lock().unwrap();
+
+        Ok(match offset {
+            UART_DATA => port.input.pop_front().unwrap_or(0),
+            _ if offset & !3 == UART_STATUS => read_register_byte(port.status(), offset),
+            _ if offset & !3 == UART_BAUD => read_register_byte(self.baud, offset),
+            _ => 0,
+        })
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(UART_ADDRESS);
+
+        if offset >= UART_SIZE {
+            return Err(MemoryUnmapped(address));
+        }
+
+        let mut port = self.port.lock().unwrap();
+
+        match offset {
+            // A full buffer drops the byte, programs should check UART_TX_READY.
+            UART_DATA => {
+                if port.output.len() < UART_BUFFER_SIZE {
+                    port.output.push_back(value)
+                }
+            }
+            _ if offset & !3 == UART_STATUS => port.overflow = false,
+            _ if offset & !3 == UART_BAUD => {
+                self.baud = write_register_byte(self.baud, offset, value)
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.baud = UART_DEFAULT_BAUD;
+        self.port.lock().unwrap().clear();
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        let port = self.port.lock().unwrap();
+
+        serde_json::to_value(UartDisplay {
+            baud: self.baud,
+            connected: port.connected(),
+            input: port.input.len(),
+            output: port.output.len(),
+        })
+        .ok()
+    }
+}
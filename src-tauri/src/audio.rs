@@ -0,0 +1,85 @@
+use crate::access_manager::{AccessFilter, AccessManager};
+use crate::state::DebuggerBody;
+use std::path::PathBuf;
+use tauri::http::method::Method;
+use tauri::http::{Request, Response};
+use tauri::{Manager, UriSchemeContext, Wry};
+
+#[tauri::command]
+pub fn start_audio_capture(state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return;
+    };
+
+    pointer.start_audio_capture()
+}
+
+#[tauri::command]
+pub async fn save_audio_capture(
+    state: tauri::State<'_, DebuggerBody>,
+    access: tauri::State<'_, AccessManager>,
+) -> Result<Option<PathBuf>, ()> {
+    let capture = {
+        let Some(pointer) = &*state.lock().unwrap() else {
+            return Ok(None);
+        };
+
+        pointer.take_audio_capture()
+    };
+
+    let Some(capture) = capture else {
+        return Ok(None);
+    };
+
+    let filters = [AccessFilter {
+        name: "WAV".to_string(),
+        extensions: vec!["wav".to_string()],
+    }];
+
+    let Some(path) = access.select_save("Save Audio", &filters, false).await else {
+        return Ok(None);
+    };
+
+    let capture = capture.lock().unwrap();
+
+    capture.save(&path).map(|_| Some(path)).ok_or(())
+}
+
+// Body is the oldest buffer the program played as a WAV file, 204 if there is nothing new.
+pub fn audio_protocol(
+    context: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let app = context.app_handle();
+
+    // Disable CORS, nothing super private here.
+    let builder = Response::builder()
+        .header("Access-Control-Allow-Headers", "*")
+        .header("Access-Control-Allow-Origin", "*");
+
+    // Check for preflight, very primitive check.
+    if request.method() == Method::OPTIONS {
+        return builder.body(vec![]).expect("Failed to build response");
+    }
+
+    let state: tauri::State<'_, DebuggerBody> = app.state();
+
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return builder
+            .status(400)
+            .body(vec![])
+            .expect("Failed to build response");
+    };
+
+    let Some(clip) = pointer.take_audio() else {
+        return builder
+            .status(204)
+            .body(vec![])
+            .expect("Failed to build response");
+    };
+
+    builder
+        .header("Content-Type", "audio/wav")
+        .body(clip.encode_wav())
+        .expect("Failed to build response")
+}
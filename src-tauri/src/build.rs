@@ -1,16 +1,22 @@
 use crate::midi::ForwardMidi;
 use crate::state::DebuggerBody;
 use crate::time::TokioTimeHandler;
+use crate::uart::UartBridge;
 use saturn_backend::build::{
-    assemble_text, configure_keyboard, create_elf_state, get_binary_finished_pcs,
-    get_elf_finished_pcs, AssemblerResult, DisassembleResult, PrintPayload,
+    assemble_text, configure_devices, create_elf_state, get_binary_finished_pcs,
+    get_elf_finished_pcs, source_lines, AssemblerResult, DisassembleResult, PrintPayload,
     TIME_TRAVEL_HISTORY_SIZE,
 };
-use saturn_backend::device::{setup_state, state_from_binary, ExecutionState};
+use saturn_backend::checker::{CheckOptions, Checker};
+use saturn_backend::device::{state_from_binary, ExecutionState};
 use saturn_backend::execution::RewindableDevice;
-use saturn_backend::keyboard::KeyboardState;
+use saturn_backend::layout::{LayoutPreset, MemoryLayout};
+use saturn_backend::mmio::{MmioHandler, Peripherals};
 use saturn_backend::regions::{AssembleRegionsOptions, AssembledRegions};
+use saturn_backend::registry::{DeviceKind, MmioDevice};
 use saturn_backend::syscall::{ConsoleHandler, MidiHandler, SyscallState, TimeHandler};
+use saturn_backend::uart::UartHandler;
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::path::Path;
 use std::sync::{Arc, Mutex, MutexGuard};
@@ -38,6 +44,18 @@ fn forward_print(app: tauri::AppHandle<Wry>) -> Box<dyn ConsoleHandler + Send +
     Box::new(ForwardPrinter { app })
 }
 
+// Built in devices plus the UART, wired to whatever the bridge is connected to.
+fn configure_desktop_peripherals(
+    memory: &mut SectionMemory<MmioHandler>,
+    layout: &MemoryLayout,
+    kinds: &[DeviceKind],
+    uart: &UartBridge,
+) -> Result<Peripherals, String> {
+    let devices: Vec<Box<dyn MmioDevice>> = vec![Box::new(UartHandler::new(uart.port.clone()))];
+
+    configure_devices(memory, layout, kinds, devices)
+}
+
 pub fn swap<
     Listen: ListenResponder + Send + 'static,
     Track: Tracker<SectionMemory<Listen>> + Send + 'static,
@@ -45,11 +63,13 @@ pub fn swap<
     mut pointer: MutexGuard<Option<Arc<dyn RewindableDevice>>>,
     debugger: Executor<SectionMemory<Listen>, Track>,
     finished_pcs: Vec<u32>,
-    keyboard: Arc<Mutex<KeyboardState>>,
+    peripherals: Peripherals,
     console: Box<dyn ConsoleHandler + Send + Sync>,
     midi: Box<dyn MidiHandler + Send + Sync>,
     time: Arc<dyn TimeHandler + Send + Sync>,
     current_directory: Option<String>,
+    layout: &MemoryLayout,
+    checker: Option<Checker>,
 ) {
     if let Some(state) = pointer.as_ref() {
         state.pause();
@@ -61,12 +81,14 @@ pub fn swap<
         midi,
         time,
         current_directory,
+        layout,
+        checker,
     )));
 
     // Drop should cancel the last process and kill the other thread.
     *pointer = Some(Arc::new(ExecutionState {
         debugger: wrapped,
-        keyboard,
+        peripherals,
         delegate,
         finished_pcs,
     }));
@@ -76,11 +98,13 @@ pub fn swap_watched<Mem: Memory + Send + 'static>(
     mut pointer: MutexGuard<Option<Arc<dyn RewindableDevice>>>,
     debugger: Executor<WatchedMemory<Mem>, HistoryTracker>,
     finished_pcs: Vec<u32>,
-    keyboard: Arc<Mutex<KeyboardState>>,
+    peripherals: Peripherals,
     console: Box<dyn ConsoleHandler + Send + Sync>,
     midi: Box<dyn MidiHandler + Send + Sync>,
     time: Arc<dyn TimeHandler + Send + Sync>,
     current_directory: Option<String>,
+    layout: &MemoryLayout,
+    checker: Option<Checker>,
 ) {
     if let Some(state) = pointer.as_ref() {
         state.pause();
@@ -92,12 +116,14 @@ pub fn swap_watched<Mem: Memory + Send + 'static>(
         midi,
         time,
         current_directory,
+        layout,
+        checker,
     )));
 
     // Drop should cancel the last process and kill the other thread.
     *pointer = Some(Arc::new(ExecutionState {
         debugger: wrapped,
-        keyboard,
+        peripherals,
         delegate,
         finished_pcs,
     }));
@@ -108,7 +134,11 @@ pub fn configure_elf(
     bytes: Vec<u8>,
     time_travel: bool,
     path: Option<String>,
+    layout: Option<LayoutPreset>,
+    checks: Option<CheckOptions>,
+    devices: Option<Vec<DeviceKind>>, // every built in device if missing
     state: tauri::State<'_, DebuggerBody>,
+    uart: tauri::State<'_, UartBridge>,
     app_handle: tauri::AppHandle<Wry>,
 ) -> bool {
     let Ok(elf) = Elf::read(&mut Cursor::new(bytes)) else {
@@ -121,9 +151,14 @@ pub fn configure_elf(
     let midi = Box::new(ForwardMidi::new(app_handle));
     let time = Arc::new(TokioTimeHandler::new());
     let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
+    let layout = layout.unwrap_or_default().layout();
+    let checker = Checker::new(checks.unwrap_or_default(), &layout, HashMap::new());
 
     let mut memory = SectionMemory::new();
-    let keyboard = configure_keyboard(&mut memory);
+    let kinds = devices.unwrap_or_else(|| DeviceKind::ALL.to_vec());
+    let Ok(peripherals) = configure_desktop_peripherals(&mut memory, &layout, &kinds, &uart) else {
+        return false;
+    };
 
     let current_directory = path.and_then(|x| {
         Path::new(&x)
@@ -134,32 +169,34 @@ pub fn configure_elf(
     if time_travel {
         let memory = WatchedMemory::new(memory);
 
-        let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-        setup_state(&mut cpu_state);
+        let cpu_state = create_elf_state(&elf, &layout, memory);
 
         swap_watched(
             state.lock().unwrap(),
             Executor::new(cpu_state, history),
             finished_pcs,
-            keyboard,
+            peripherals,
             console,
             midi,
             time,
             current_directory,
+            &layout,
+            checker,
         );
     } else {
-        let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-        setup_state(&mut cpu_state);
+        let cpu_state = create_elf_state(&elf, &layout, memory);
 
         swap(
             state.lock().unwrap(),
             Executor::new(cpu_state, EmptyTracker {}),
             finished_pcs,
-            keyboard,
+            peripherals,
             console,
             midi,
             time,
             current_directory,
+            &layout,
+            checker,
         );
     }
 
@@ -171,7 +208,11 @@ pub fn configure_asm(
     text: &str,
     path: Option<String>,
     time_travel: bool,
+    layout: Option<LayoutPreset>,
+    checks: Option<CheckOptions>,
+    devices: Option<Vec<DeviceKind>>, // every built in device if missing
     state: tauri::State<'_, DebuggerBody>,
+    uart: tauri::State<'_, UartBridge>,
     app_handle: tauri::AppHandle<Wry>,
 ) -> AssemblerResult {
     let binary = assemble_text(text, path.as_ref().map(|x| x.as_str()));
@@ -186,9 +227,25 @@ pub fn configure_asm(
     let midi = Box::new(ForwardMidi::new(app_handle));
     let time = Arc::new(TokioTimeHandler::new());
     let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
+    let layout = layout.unwrap_or_default().layout();
+    let checker = Checker::new(
+        checks.unwrap_or_default(),
+        &layout,
+        source_lines(&binary, text),
+    );
 
     let mut memory = SectionMemory::new();
-    let keyboard = configure_keyboard(&mut memory);
+    let kinds = devices.unwrap_or_else(|| DeviceKind::ALL.to_vec());
+    let peripherals = match configure_desktop_peripherals(&mut memory, &layout, &kinds, &uart) {
+        Ok(peripherals) => peripherals,
+        Err(message) => {
+            return AssemblerResult::Error {
+                marker: None,
+                message,
+                body: None,
+            }
+        }
+    };
 
     let current_directory = path.and_then(|x| {
         Path::new(&x)
@@ -199,32 +256,34 @@ pub fn configure_asm(
     if time_travel {
         let memory = WatchedMemory::new(memory);
 
-        let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-        setup_state(&mut cpu_state);
+        let cpu_state = state_from_binary(binary, &layout, memory);
 
         swap_watched(
             state.lock().unwrap(),
             Executor::new(cpu_state, history),
             finished_pcs,
-            keyboard,
+            peripherals,
             console,
             midi,
             time,
             current_directory,
+            &layout,
+            checker,
         );
     } else {
-        let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-        setup_state(&mut cpu_state);
+        let cpu_state = state_from_binary(binary, &layout, memory);
 
         swap(
             state.lock().unwrap(),
             Executor::new(cpu_state, EmptyTracker {}),
             finished_pcs,
-            keyboard,
+            peripherals,
             console,
             midi,
             time,
             current_directory,
+            &layout,
+            checker,
         );
     }
 
@@ -1,34 +1,123 @@
+use crate::access_manager::{AccessFilter, AccessManager};
 use crate::state::DebuggerBody;
 use num::FromPrimitive;
-use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
+use saturn_backend::display::{
+    FlushDisplayBody, FlushDisplayState, FlushDisplaysBody, PixelFormat, MAIN_DISPLAY,
+};
 use saturn_backend::execution::ReadDisplayTarget;
+use saturn_backend::recording::{Recording, RecordingFormat};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tauri::http::method::Method;
 use tauri::http::{Request, Response};
 use tauri::{Manager, UriSchemeContext, Wry};
 use titan::unit::register::RegisterName;
 
+// Commands without an id act on the main display.
+fn display_id(id: Option<String>) -> String {
+    id.unwrap_or_else(|| MAIN_DISPLAY.to_string())
+}
+
+fn find_display(state: &FlushDisplaysBody, id: Option<String>) -> Option<FlushDisplayBody> {
+    state.lock().unwrap().get(&display_id(id)).cloned()
+}
+
 #[tauri::command]
 pub fn configure_display(
+    id: Option<String>,
     address: u32,
     register: Option<u8>,
     width: u32,
     height: u32,
-    state: tauri::State<FlushDisplayBody>,
+    format: Option<PixelFormat>,
+    state: tauri::State<FlushDisplaysBody>,
 ) {
-    let mut body = state.lock().unwrap();
+    let mut displays = state.lock().unwrap();
+
+    let display = displays
+        .entry(display_id(id))
+        .or_insert_with(|| Arc::new(Mutex::new(FlushDisplayState::default())));
+
+    let mut body = display.lock().unwrap();
 
     *body = FlushDisplayState {
         address,
         register,
         width,
         height,
+        format: format.unwrap_or_default(),
+        scale: None,
         data: None,
+        recording: body.recording.take(), // keep recording across setting changes
     }
 }
 
 #[tauri::command]
-pub fn last_display(state: tauri::State<FlushDisplayBody>) -> FlushDisplayState {
-    state.lock().unwrap().clone()
+pub fn remove_display(id: String, state: tauri::State<FlushDisplaysBody>) {
+    // Screen and graphics devices draw to the main display, it is never removed.
+    if id == MAIN_DISPLAY {
+        return;
+    }
+
+    state.lock().unwrap().remove(&id);
+}
+
+#[tauri::command]
+pub fn display_ids(state: tauri::State<FlushDisplaysBody>) -> Vec<String> {
+    let mut ids: Vec<String> = state.lock().unwrap().keys().cloned().collect();
+
+    ids.sort();
+
+    ids
+}
+
+#[tauri::command]
+pub fn start_recording(id: Option<String>, state: tauri::State<FlushDisplaysBody>) {
+    if let Some(display) = find_display(&state, id) {
+        display.lock().unwrap().recording = Some(Arc::new(Mutex::new(Recording::new())));
+    }
+}
+
+#[tauri::command]
+pub async fn save_recording(
+    id: Option<String>,
+    format: RecordingFormat,
+    state: tauri::State<'_, FlushDisplaysBody>,
+    access: tauri::State<'_, AccessManager>,
+) -> Result<Option<Vec<PathBuf>>, ()> {
+    let Some(display) = find_display(&state, id) else {
+        return Ok(None);
+    };
+
+    let Some(recording) = display.lock().unwrap().recording.take() else {
+        return Ok(None);
+    };
+
+    let extension = match format {
+        RecordingFormat::Gif => "gif",
+        RecordingFormat::PngSequence => "png",
+    };
+
+    let filters = [AccessFilter {
+        name: extension.to_uppercase(),
+        extensions: vec![extension.to_string()],
+    }];
+
+    let Some(path) = access.select_save("Save Recording", &filters, false).await else {
+        return Ok(None);
+    };
+
+    let recording = recording.lock().unwrap();
+
+    recording.save(&path, format).map(Some).ok_or(())
+}
+
+#[tauri::command]
+pub fn last_display(
+    id: Option<String>,
+    state: tauri::State<FlushDisplaysBody>,
+) -> Option<FlushDisplayState> {
+    find_display(&state, id).map(|display| display.lock().unwrap().clone())
 }
 
 pub fn display_protocol(
@@ -40,16 +129,36 @@ pub fn display_protocol(
     // Disable CORS, nothing super private here.
     let builder = Response::builder()
         .header("Access-Control-Allow-Headers", "*")
-        .header("Access-Control-Allow-Origin", "*");
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Expose-Headers", "width, height, scale");
 
     // Check for preflight, very primitive check.
     if request.method() == Method::OPTIONS {
         return builder.body(vec![]).expect("Failed to build response");
     }
 
-    let grab_params = || -> Option<(u32, u32, ReadDisplayTarget)> {
-        let headers = request.headers();
+    let headers = request.headers();
 
+    // Displays with an id use their stored configuration, the rest comes from headers.
+    let id = headers
+        .get("id")
+        .and_then(|id| id.to_str().ok())
+        .map(|id| id.to_string());
+
+    let grab_stored = |id: String| -> Option<(u32, u32, ReadDisplayTarget, PixelFormat)> {
+        let displays: tauri::State<'_, FlushDisplaysBody> = app.state();
+        let display = find_display(&displays, Some(id))?;
+        let display = display.lock().unwrap();
+
+        let target = match display.register.and_then(RegisterName::from_u8) {
+            Some(register) => ReadDisplayTarget::Register(register),
+            None => ReadDisplayTarget::Address(display.address),
+        };
+
+        Some((display.width, display.height, target, display.format))
+    };
+
+    let grab_params = || -> Option<(u32, u32, ReadDisplayTarget, PixelFormat)> {
         let width = headers.get("width")?.to_str().ok()?;
         let height = headers.get("height")?.to_str().ok()?;
         // address is still required as fallback
@@ -63,10 +172,30 @@ pub fn display_protocol(
             ReadDisplayTarget::Address(address.parse().ok()?)
         };
 
-        Some((width.parse().ok()?, height.parse().ok()?, target))
+        // format is optional, palette is only needed for palette8
+        let format = match headers.get("format") {
+            Some(format) => {
+                let palette = match headers.get("palette") {
+                    Some(palette) => palette.to_str().ok()?.parse().ok()?,
+                    None => 0,
+                };
+
+                PixelFormat::from_name(format.to_str().ok()?, palette)?
+            }
+            None => PixelFormat::default(),
+        };
+
+        Some((width.parse().ok()?, height.parse().ok()?, target, format))
     };
 
-    let Some((width, height, address)) = grab_params() else {
+    let main = id.as_deref().map(|id| id == MAIN_DISPLAY).unwrap_or(true);
+
+    let params = match id {
+        Some(id) => grab_stored(id),
+        None => grab_params(),
+    };
+
+    let Some((width, height, address, format)) = params else {
         return builder
             .status(400)
             .body(vec![])
@@ -82,12 +211,22 @@ pub fn display_protocol(
             .expect("Failed to build response");
     };
 
-    let Some(result) = pointer.read_display(address, width, height) else {
+    let Some(frame) = pointer.read_display(address, width, height, format, main) else {
         return builder
             .status(400)
             .body(vec![])
             .expect("Failed to build response");
     };
 
-    builder.body(result).expect("Failed to build response")
+    // The program may have picked its own resolution and scale.
+    let builder = builder
+        .header("width", frame.width.to_string())
+        .header("height", frame.height.to_string());
+
+    let builder = match frame.scale {
+        Some(scale) => builder.header("scale", scale.to_string()),
+        None => builder,
+    };
+
+    builder.body(frame.data).expect("Failed to build response")
 }
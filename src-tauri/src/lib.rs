@@ -4,6 +4,7 @@
 )]
 
 mod access_manager;
+mod audio;
 mod build;
 mod debug;
 mod decode;
@@ -13,7 +14,9 @@ mod menu;
 mod midi;
 mod state;
 mod testing;
+mod text;
 mod time;
+mod uart;
 mod watch;
 
 use crate::access_manager::{
@@ -24,8 +27,9 @@ use std::sync::{Arc, Mutex};
 use tauri::WindowEvent::{Destroyed, DragDrop};
 use tauri::{DragDropEvent, Manager};
 
+use crate::audio::{audio_protocol, save_audio_capture, start_audio_capture};
 use crate::menu::{create_menu, handle_event};
-use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
+use saturn_backend::display::{single_display, FlushDisplaysBody};
 
 use crate::build::{
     assemble, assemble_binary, assemble_regions, configure_asm, configure_elf, disassemble,
@@ -36,11 +40,19 @@ use crate::menu::platform_shortcuts;
 use crate::midi::{midi_install, midi_protocol, MidiProviderContainer};
 use crate::state::DebuggerBody;
 
-use crate::state::{last_pc, pause, post_input, post_key, resume, rewind, stop, wake_sync};
-use crate::testing::{all_tests, run_tests};
+use crate::state::{
+    last_pc, pause, post_input, post_key, post_key_code, post_keypad, post_mouse, post_switch,
+    read_devices, read_heap, read_lab, resume, rewind, stop, wake_refresh, wake_sync,
+};
+use crate::testing::{all_tests, golden_audio, golden_recording, golden_test, run_tests};
 
 use crate::decode::{decode_instruction, detailed_disassemble};
-use crate::display::{configure_display, display_protocol, last_display};
+use crate::display::{
+    configure_display, display_ids, display_protocol, last_display, remove_display, save_recording,
+    start_recording,
+};
+use crate::text::text_protocol;
+use crate::uart::{uart_close, uart_listen, uart_open_pty, uart_status, UartBridge};
 
 #[tauri::command]
 fn is_debug() -> bool {
@@ -53,8 +65,9 @@ pub fn run() {
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_dialog::init())
         .manage(Mutex::new(None) as DebuggerBody)
-        .manage(Arc::new(Mutex::new(FlushDisplayState::default())) as FlushDisplayBody)
+        .manage(Arc::new(Mutex::new(single_display(Default::default()))) as FlushDisplaysBody)
         .manage(Mutex::new(MidiProviderContainer::None))
+        .manage(UartBridge::new())
         .menu(create_menu)
         .setup(|app| {
             app.manage(AccessManager::load(app.handle().clone()));
@@ -80,26 +93,44 @@ pub fn run() {
         })
         .on_menu_event(handle_event)
         .invoke_handler(tauri::generate_handler![
-            platform_shortcuts, // util
-            assemble,           // build
-            disassemble,        // build
-            assemble_binary,    // build
-            assemble_regions,   // build
-            configure_elf,      // build
-            configure_asm,      // build
-            resume,             // execution
-            rewind,             // execution
-            pause,              // execution
-            stop,               // execution
-            last_pc,            // execution
-            read_bytes,         // debug
-            write_bytes,        // debug
-            set_register,       // debug
-            swap_breakpoints,   // debug
-            post_key,           // bitmap
-            post_input,         // bitmap
-            configure_display,  // bitmap
-            last_display,       // bitmap
+            platform_shortcuts,  // util
+            assemble,            // build
+            disassemble,         // build
+            assemble_binary,     // build
+            assemble_regions,    // build
+            configure_elf,       // build
+            configure_asm,       // build
+            resume,              // execution
+            rewind,              // execution
+            pause,               // execution
+            stop,                // execution
+            last_pc,             // execution
+            read_bytes,          // debug
+            write_bytes,         // debug
+            set_register,        // debug
+            swap_breakpoints,    // debug
+            post_key,            // bitmap
+            post_key_code,       // bitmap
+            post_mouse,          // bitmap
+            post_input,          // bitmap
+            configure_display,   // bitmap
+            last_display,        // bitmap
+            remove_display,      // bitmap
+            display_ids,         // bitmap
+            wake_refresh,        // bitmap
+            start_recording,     // bitmap
+            save_recording,      // bitmap
+            post_keypad,         // lab
+            post_switch,         // lab
+            read_lab,            // lab
+            read_devices,        // devices
+            read_heap,           // debug
+            start_audio_capture, // audio
+            save_audio_capture,  // audio
+            uart_listen,         // uart
+            uart_open_pty,       // uart
+            uart_close,          // uart
+            uart_status,         // uart
             access_sync,
             access_select_save,
             access_select_open,
@@ -111,6 +142,9 @@ pub fn run() {
             wake_sync,
             all_tests,
             run_tests,
+            golden_test,
+            golden_audio,
+            golden_recording,
             decode_instruction,
             detailed_disassemble,
             export_hex_regions,
@@ -119,6 +153,8 @@ pub fn run() {
         ])
         .register_uri_scheme_protocol("midi", midi_protocol)
         .register_uri_scheme_protocol("display", display_protocol)
+        .register_uri_scheme_protocol("text", text_protocol)
+        .register_uri_scheme_protocol("audio", audio_protocol)
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
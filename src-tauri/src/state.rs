@@ -1,5 +1,8 @@
-use saturn_backend::display::FlushDisplayBody;
+use saturn_backend::display::FlushDisplaysBody;
 use saturn_backend::execution::{BatchOptions, ResumeOptions, ResumeResult, RewindableDevice};
+use saturn_backend::heap::HeapReport;
+use saturn_backend::lab::LabDisplay;
+use saturn_backend::registry::DeviceSnapshot;
 use std::sync::{Arc, Mutex};
 use titan::execution::executor::ExecutorMode;
 
@@ -20,7 +23,7 @@ pub async fn resume(
     count: Option<usize>,
     breakpoints: Option<Vec<u32>>,
     state: tauri::State<'_, DebuggerBody>,
-    display: tauri::State<'_, FlushDisplayBody>,
+    displays: tauri::State<'_, FlushDisplaysBody>,
 ) -> Result<ResumeResult, ()> {
     let context = {
         let Some(pointer) = &*state.lock().unwrap() else {
@@ -30,10 +33,11 @@ pub async fn resume(
         pointer.clone()
     };
 
-    let display = display.inner().clone();
+    // Snapshot, displays configured while running are picked up on the next resume.
+    let displays = displays.lock().unwrap().clone();
 
     tokio::spawn(async move {
-        //count, breakpoints, Some(display), true
+        //count, breakpoints, displays, true
         context
             .resume(ResumeOptions {
                 batch: count.map(|count| BatchOptions {
@@ -43,7 +47,7 @@ pub async fn resume(
                     break_at_end: true,
                 }),
                 breakpoints,
-                display: Some(display),
+                displays,
                 change_state: if count.is_none() {
                     Some(ExecutorMode::Running)
                 } else {
@@ -90,6 +94,57 @@ pub fn post_key(key: char, up: bool, state: tauri::State<'_, DebuggerBody>) {
     pointer.post_key(key, up)
 }
 
+#[tauri::command]
+pub fn post_key_code(code: &str, up: bool, state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return;
+    };
+
+    pointer.post_key_code(code, up)
+}
+
+#[tauri::command]
+pub fn post_mouse(x: i32, y: i32, buttons: u32, state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return;
+    };
+
+    pointer.post_mouse(x, y, buttons)
+}
+
+#[tauri::command]
+pub fn post_keypad(key: u8, up: bool, state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return;
+    };
+
+    pointer.post_keypad(key, up)
+}
+
+#[tauri::command]
+pub fn post_switch(index: u8, on: bool, state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return;
+    };
+
+    pointer.post_switch(index, on)
+}
+
+#[tauri::command]
+pub fn read_lab(state: tauri::State<'_, DebuggerBody>) -> Option<LabDisplay> {
+    Some(state.lock().unwrap().as_ref()?.read_lab())
+}
+
+#[tauri::command]
+pub fn read_devices(state: tauri::State<'_, DebuggerBody>) -> Option<Vec<DeviceSnapshot>> {
+    Some(state.lock().unwrap().as_ref()?.read_devices())
+}
+
+#[tauri::command]
+pub fn read_heap(state: tauri::State<'_, DebuggerBody>) -> Option<HeapReport> {
+    Some(state.lock().unwrap().as_ref()?.read_heap())
+}
+
 #[tauri::command]
 pub fn post_input(text: String, state: tauri::State<'_, DebuggerBody>) {
     let Some(pointer) = &*state.lock().unwrap() else {
@@ -107,3 +162,12 @@ pub fn wake_sync(state: tauri::State<'_, DebuggerBody>) {
 
     pointer.wake_sync()
 }
+
+#[tauri::command]
+pub fn wake_refresh(state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return;
+    };
+
+    pointer.wake_refresh()
+}
@@ -1,6 +1,22 @@
 // use std::panic;
 // use std::panic::UnwindSafe;
+use crate::access_manager::{AccessFilter, AccessManager};
+use crate::time::TokioTimeHandler;
+use saturn_backend::build::assemble_text;
+use saturn_backend::display::{FlushDisplayState, FlushDisplaysBody, MAIN_DISPLAY};
+use saturn_backend::execution::ExecutionDevice;
+use saturn_backend::golden::{
+    capture, capture_audio, capture_recording, compare, golden_device, CapturePoint, GoldenDevice,
+    GoldenImage, GoldenResult,
+};
+use saturn_backend::layout::LayoutPreset;
+use saturn_backend::recording::RecordingFormat;
 use serde::Serialize;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Serialize)]
 pub enum TestResult {
@@ -113,3 +129,242 @@ pub fn run_tests(_: &str) -> Vec<TestItem> {
         // TestItem { name: "Preserves Saved Registers".to_string(), result: run_test(|| tests::preserves_saved(path)) },
     ]
 }
+
+#[derive(Serialize)]
+pub struct GoldenReport {
+    #[serde(flatten)]
+    result: GoldenResult,
+    reference: PathBuf,
+    diff_path: Option<PathBuf>, // written next to the reference on failure
+}
+
+// Programs that never reach the capture point, like a game loop waiting for input, are stopped after this.
+const GOLDEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Pauses the device if run takes longer than limit. The run yields between slices, so this can fire.
+async fn within<T>(
+    device: &GoldenDevice,
+    run: impl Future<Output = Result<T, String>>,
+    limit: Duration,
+) -> Result<T, String> {
+    match tokio::time::timeout(limit, run).await {
+        Ok(result) => result,
+        Err(_) => {
+            device.pause();
+
+            Err("The program did not reach the capture point in time.".to_string())
+        }
+    }
+}
+
+// Same settings as the main display, but flushing it must not touch what the user sees.
+fn headless_display(displays: &FlushDisplaysBody) -> Result<FlushDisplayState, String> {
+    let displays = displays.lock().unwrap();
+
+    let main = displays
+        .get(MAIN_DISPLAY)
+        .ok_or_else(|| "The main display is not configured.".to_string())?;
+
+    let main = main.lock().unwrap();
+
+    Ok(FlushDisplayState {
+        data: None,
+        recording: None,
+        ..main.clone()
+    })
+}
+
+// Runs the program in its own session to the capture point and compares the main display against a reference PNG.
+// Without a reference one is picked, None if that was cancelled.
+#[tauri::command]
+pub async fn golden_test(
+    text: &str,
+    path: Option<&str>,
+    point: CapturePoint,
+    reference: Option<PathBuf>,
+    tolerance: u8,
+    layout: Option<LayoutPreset>,
+    displays: tauri::State<'_, FlushDisplaysBody>,
+    access: tauri::State<'_, AccessManager>,
+) -> Result<Option<GoldenReport>, String> {
+    let binary = assemble_text(text, path).map_err(|_| "Failed to assemble.".to_string())?;
+    let breakpoint = point.breakpoint(&binary.labels)?;
+
+    let reference = match reference {
+        Some(reference) => reference,
+        None => {
+            let filters = [AccessFilter {
+                name: "PNG".to_string(),
+                extensions: vec!["png".to_string()],
+            }];
+
+            let Some(reference) = access
+                .select_open("Select Reference", &filters, false)
+                .await
+            else {
+                return Ok(None);
+            };
+
+            reference
+        }
+    };
+
+    let bytes = fs::read(&reference).map_err(|e| e.to_string())?;
+    let expected = GoldenImage::decode_png(&bytes)
+        .ok_or_else(|| "The reference is not a valid PNG.".to_string())?;
+
+    let layout = layout.unwrap_or_default().layout();
+    let device = golden_device(binary, &layout, Arc::new(TokioTimeHandler::new()))?;
+
+    let display = headless_display(&displays)?;
+
+    let capture = capture(&device, breakpoint, Arc::new(Mutex::new(display)));
+    let actual = within(&device, capture, GOLDEN_TIMEOUT).await?;
+    let result = compare(&actual, &expected, tolerance);
+
+    let diff_path = match &result.diff {
+        Some(diff) => {
+            let stem = reference.file_stem().unwrap_or_default().to_string_lossy();
+            let diff_path = reference.with_file_name(format!("{}_diff.png", stem));
+
+            let bytes = diff
+                .encode_png()
+                .ok_or_else(|| "Failed to encode the diff.".to_string())?;
+
+            fs::write(&diff_path, bytes).map_err(|e| e.to_string())?;
+
+            Some(diff_path)
+        }
+        None => None,
+    };
+
+    Ok(Some(GoldenReport {
+        result,
+        reference,
+        diff_path,
+    }))
+}
+
+// Runs the program in its own session to the capture point and saves everything it played as a WAV file.
+// Without an output one is picked, None if that was cancelled.
+#[tauri::command]
+pub async fn golden_audio(
+    text: &str,
+    path: Option<&str>,
+    point: CapturePoint,
+    output: Option<PathBuf>,
+    layout: Option<LayoutPreset>,
+    access: tauri::State<'_, AccessManager>,
+) -> Result<Option<PathBuf>, String> {
+    let binary = assemble_text(text, path).map_err(|_| "Failed to assemble.".to_string())?;
+    let breakpoint = point.breakpoint(&binary.labels)?;
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let filters = [AccessFilter {
+                name: "WAV".to_string(),
+                extensions: vec!["wav".to_string()],
+            }];
+
+            let Some(output) = access.select_save("Save Audio", &filters, false).await else {
+                return Ok(None);
+            };
+
+            output
+        }
+    };
+
+    let layout = layout.unwrap_or_default().layout();
+    let device = golden_device(binary, &layout, Arc::new(TokioTimeHandler::new()))?;
+
+    let capture = capture_audio(&device, breakpoint, &output);
+
+    if !within(&device, capture, GOLDEN_TIMEOUT).await? {
+        return Err("The program did not play any audio.".to_string());
+    }
+
+    Ok(Some(output))
+}
+
+// Runs the program in its own session to the capture point and saves a recording of the main display.
+// Without an output one is picked, None if that was cancelled.
+#[tauri::command]
+pub async fn golden_recording(
+    text: &str,
+    path: Option<&str>,
+    point: CapturePoint,
+    output: Option<PathBuf>,
+    format: RecordingFormat,
+    layout: Option<LayoutPreset>,
+    displays: tauri::State<'_, FlushDisplaysBody>,
+    access: tauri::State<'_, AccessManager>,
+) -> Result<Option<Vec<PathBuf>>, String> {
+    let binary = assemble_text(text, path).map_err(|_| "Failed to assemble.".to_string())?;
+    let breakpoint = point.breakpoint(&binary.labels)?;
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            let extension = match format {
+                RecordingFormat::Gif => "gif",
+                RecordingFormat::PngSequence => "png",
+            };
+
+            let filters = [AccessFilter {
+                name: extension.to_uppercase(),
+                extensions: vec![extension.to_string()],
+            }];
+
+            let Some(output) = access.select_save("Save Recording", &filters, false).await else {
+                return Ok(None);
+            };
+
+            output
+        }
+    };
+
+    let layout = layout.unwrap_or_default().layout();
+    let device = golden_device(binary, &layout, Arc::new(TokioTimeHandler::new()))?;
+    let display = headless_display(&displays)?;
+
+    let capture = capture_recording(
+        &device,
+        breakpoint,
+        Arc::new(Mutex::new(display)),
+        &output,
+        format,
+    );
+
+    within(&device, capture, GOLDEN_TIMEOUT).await.map(Some)
+}
+
+#[cfg(test)]
+mod golden_tests {
+    use super::*;
+
+    // Polls the keyboard forever, slices never end on their own.
+    const POLLING: &str = "
+        lui $t1, 0xFFFF
+    loop:
+        lw $t0, 0($t1)
+        j loop
+    ";
+
+    #[tokio::test]
+    async fn timeout_stops_a_program_that_never_finishes() {
+        let binary = assemble_text(POLLING, None).unwrap_or_else(|_| panic!("Failed to assemble"));
+        let layout = LayoutPreset::default().layout();
+        let device = golden_device(binary, &layout, Arc::new(TokioTimeHandler::new())).unwrap();
+
+        let display = Arc::new(Mutex::new(FlushDisplayState::default()));
+        let run = capture(&device, None, display);
+
+        let result = within(&device, run, Duration::from_millis(200)).await;
+
+        assert_eq!(
+            result.err().as_deref(),
+            Some("The program did not reach the capture point in time.")
+        );
+    }
+}
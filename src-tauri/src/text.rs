@@ -0,0 +1,46 @@
+use crate::state::DebuggerBody;
+use tauri::http::method::Method;
+use tauri::http::{Request, Response};
+use tauri::{Manager, UriSchemeContext, Wry};
+
+// Body is the cells of the text display, the size and cursor are sent as headers.
+pub fn text_protocol(
+    context: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let app = context.app_handle();
+
+    // Disable CORS, nothing super private here.
+    let builder = Response::builder()
+        .header("Access-Control-Allow-Headers", "*")
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Expose-Headers", "columns, rows, cursor");
+
+    // Check for preflight, very primitive check.
+    if request.method() == Method::OPTIONS {
+        return builder.body(vec![]).expect("Failed to build response");
+    }
+
+    let state: tauri::State<'_, DebuggerBody> = app.state();
+
+    let Some(pointer) = &*state.lock().unwrap() else {
+        return builder
+            .status(400)
+            .body(vec![])
+            .expect("Failed to build response");
+    };
+
+    let text = pointer.read_text();
+
+    // Hidden cursors are left out.
+    let builder = match text.cursor {
+        Some((column, row)) => builder.header("cursor", format!("{column},{row}")),
+        None => builder,
+    };
+
+    builder
+        .header("columns", text.columns.to_string())
+        .header("rows", text.rows.to_string())
+        .body(text.cells)
+        .expect("Failed to build response")
+}
@@ -0,0 +1,274 @@
+use saturn_backend::uart::{UartPort, UartPortBody};
+use serde::Serialize;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// How long the bridge sleeps when nothing moved in either direction.
+const UART_POLL: Duration = Duration::from_millis(5);
+
+#[derive(Clone, Serialize)]
+pub struct UartStatus {
+    pub address: Option<String>, // host:port for TCP, the device path for a PTY
+    pub connected: bool,
+}
+
+struct UartLink {
+    address: String,
+    stop: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>, // per link, so a link on its way out cannot clear the next one's
+}
+
+// Host end of the UART device, the port is shared by every program that runs.
+pub struct UartBridge {
+    pub port: UartPortBody,
+    link: Mutex<Option<UartLink>>,
+}
+
+impl UartBridge {
+    pub fn new() -> UartBridge {
+        UartBridge {
+            port: Arc::new(Mutex::new(UartPort::new())),
+            link: Mutex::new(None),
+        }
+    }
+
+    fn close(&self) {
+        if let Some(link) = self.link.lock().unwrap().take() {
+            link.stop.store(true, Ordering::Relaxed)
+        }
+
+        self.port.lock().unwrap().attach(None)
+    }
+
+    // Replaces the current link, returns the flag set when the new one should stop
+    // and the flag the new one keeps up to date while something is attached.
+    fn open(&self, address: String) -> (Arc<AtomicBool>, Arc<AtomicBool>) {
+        self.close();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let connected = Arc::new(AtomicBool::new(false));
+
+        *self.link.lock().unwrap() = Some(UartLink {
+            address,
+            stop: stop.clone(),
+            connected: connected.clone(),
+        });
+
+        self.port.lock().unwrap().attach(Some(connected.clone()));
+
+        (stop, connected)
+    }
+}
+
+// Streams are non-blocking, so retry until everything is out.
+fn send(stream: &mut impl Write, mut bytes: &[u8], stop: &AtomicBool) -> bool {
+    while !bytes.is_empty() && !stop.load(Ordering::Relaxed) {
+        match stream.write(bytes) {
+            Ok(0) => return false,
+            Ok(count) => bytes = &bytes[count..],
+            Err(error) if error.kind() == ErrorKind::WouldBlock => thread::sleep(UART_POLL),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return false,
+        }
+    }
+
+    true
+}
+
+// Moves bytes both ways until the bridge is stopped or the other end hangs up.
+fn pump(stream: &mut (impl Read + Write), port: &UartPortBody, stop: &AtomicBool) {
+    let mut buffer = [0u8; 512];
+
+    while !stop.load(Ordering::Relaxed) {
+        let mut idle = true;
+
+        match stream.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(count) => {
+                port.lock().unwrap().push_input(&buffer[..count]);
+
+                idle = false
+            }
+            Err(error)
+                if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {}
+            Err(_) => return,
+        }
+
+        let output = port.lock().unwrap().take_output();
+
+        if !output.is_empty() {
+            if !send(stream, &output, stop) {
+                return;
+            }
+
+            idle = false
+        }
+
+        if idle {
+            thread::sleep(UART_POLL)
+        }
+    }
+}
+
+// One client at a time, the next one can connect after it hangs up.
+fn serve_tcp(
+    listener: TcpListener,
+    port: UartPortBody,
+    stop: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        let Ok((mut stream, _)) = listener.accept() else {
+            thread::sleep(UART_POLL);
+
+            continue;
+        };
+
+        if stream.set_nonblocking(true).is_err() {
+            continue;
+        }
+
+        stream.set_nodelay(true).ok();
+
+        connected.store(true, Ordering::Relaxed);
+        pump(&mut stream, &port, &stop);
+        connected.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(unix)]
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::{File, OpenOptions};
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::fs::OpenOptionsExt;
+
+    // Returns the master, the slave and the slave's path.
+    pub fn open() -> Option<(File, File, String)> {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+
+            if master < 0 {
+                return None;
+            }
+
+            let master = File::from_raw_fd(master);
+            let fd = master.as_raw_fd();
+
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return None;
+            }
+
+            let name = libc::ptsname(fd);
+
+            if name.is_null() {
+                return None;
+            }
+
+            let path = CStr::from_ptr(name).to_string_lossy().to_string();
+
+            // Holding the slave open keeps reads on the master from failing while no
+            // terminal is attached. Raw, so the line discipline does not echo our output back.
+            let slave = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_NOCTTY)
+                .open(&path)
+                .ok()?;
+
+            let mut termios = std::mem::zeroed();
+
+            if libc::tcgetattr(slave.as_raw_fd(), &mut termios) == 0 {
+                libc::cfmakeraw(&mut termios);
+                libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios);
+            }
+
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+
+            if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                return None;
+            }
+
+            Some((master, slave, path))
+        }
+    }
+}
+
+// Port 0 or None picks any free port. Returns the address to connect to.
+#[tauri::command]
+pub fn uart_listen(
+    port: Option<u16>,
+    bridge: tauri::State<'_, UartBridge>,
+) -> Result<String, String> {
+    // Loopback only, the program should not be reachable from the network.
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port.unwrap_or(0)))
+        .map_err(|error| format!("Failed to listen: {}", error))?;
+
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| format!("Failed to listen: {}", error))?;
+
+    let address = listener
+        .local_addr()
+        .map_err(|error| format!("Failed to listen: {}", error))?
+        .to_string();
+
+    let (stop, connected) = bridge.open(address.clone());
+    let port = bridge.port.clone();
+
+    thread::spawn(move || serve_tcp(listener, port, stop, connected));
+
+    Ok(address)
+}
+
+// Returns the path of the terminal device, e.g. for screen or minicom.
+#[tauri::command]
+pub fn uart_open_pty(bridge: tauri::State<'_, UartBridge>) -> Result<String, String> {
+    #[cfg(unix)]
+    {
+        let (mut master, slave, path) =
+            pty::open().ok_or_else(|| "Failed to open a pseudo-terminal.".to_string())?;
+
+        let (stop, connected) = bridge.open(path.clone());
+        let port = bridge.port.clone();
+
+        thread::spawn(move || {
+            // There is no telling if a terminal has the slave open, assume one does.
+            connected.store(true, Ordering::Relaxed);
+            pump(&mut master, &port, &stop);
+            connected.store(false, Ordering::Relaxed);
+
+            drop(slave);
+        });
+
+        Ok(path)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = bridge;
+
+        Err("Pseudo-terminals are not supported on this platform.".to_string())
+    }
+}
+
+#[tauri::command]
+pub fn uart_close(bridge: tauri::State<'_, UartBridge>) {
+    bridge.close()
+}
+
+#[tauri::command]
+pub fn uart_status(bridge: tauri::State<'_, UartBridge>) -> UartStatus {
+    let link = bridge.link.lock().unwrap();
+
+    UartStatus {
+        address: link.as_ref().map(|link| link.address.clone()),
+        connected: link
+            .as_ref()
+            .is_some_and(|link| link.connected.load(Ordering::Relaxed)),
+    }
+}
@@ -8,16 +8,24 @@ use crate::midi::WasmMidi;
 use crate::time::WasmTime;
 use num::FromPrimitive;
 use saturn_backend::build::{
-    configure_keyboard, create_elf_state, get_binary_finished_pcs, get_elf_finished_pcs,
-    AssemblerResult, TIME_TRAVEL_HISTORY_SIZE,
+    configure_peripherals, create_elf_state, get_binary_finished_pcs, get_elf_finished_pcs,
+    source_lines, AssemblerResult, TIME_TRAVEL_HISTORY_SIZE,
+};
+use saturn_backend::checker::{CheckOptions, Checker};
+use saturn_backend::device::{state_from_binary, ExecutionState};
+use saturn_backend::display::{
+    single_display, DisplayFrame, FlushDisplayBody, FlushDisplayState, FlushDisplays, PixelFormat,
+    MAIN_DISPLAY,
 };
-use saturn_backend::device::{setup_state, state_from_binary, ExecutionState};
-use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
 use saturn_backend::execution::{BatchOptions, ReadDisplayTarget, ResumeOptions, RewindableDevice};
-use saturn_backend::keyboard::KeyboardState;
+use saturn_backend::layout::{LayoutPreset, MemoryLayout};
+use saturn_backend::mmio::Peripherals;
+use saturn_backend::recording::{Recording, RecordingFormat};
+use saturn_backend::registry::DeviceKind;
 use saturn_backend::syscall::{ConsoleHandler, MidiHandler, SyscallState, TimeHandler};
+use serde::Serialize;
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
@@ -89,10 +97,55 @@ pub fn detailed_disassemble(bytes: Vec<u8>) -> Result<JsValue, String> {
     Ok(serde_wasm_bindgen::to_value(&result).unwrap())
 }
 
+// Missing (undefined) formats fall back to the default 32-bit format.
+fn parse_format(format: JsValue) -> PixelFormat {
+    serde_wasm_bindgen::from_value(format).unwrap_or_default()
+}
+
+// Plain object with the pixels as a Uint8Array, serde would turn them into an array of numbers.
+fn frame_value(frame: Option<DisplayFrame>) -> JsValue {
+    let Some(frame) = frame else {
+        return JsValue::NULL;
+    };
+
+    let object = js_sys::Object::new();
+
+    let set = |key: &str, value: JsValue| {
+        js_sys::Reflect::set(&object, &JsValue::from_str(key), &value).ok();
+    };
+
+    set("width", frame.width.into());
+    set("height", frame.height.into());
+    set("scale", frame.scale.map_or(JsValue::NULL, JsValue::from));
+    set(
+        "data",
+        js_sys::Uint8Array::from(frame.data.as_slice()).into(),
+    );
+
+    object.into()
+}
+
+// Missing or malformed options leave every check off.
+fn parse_checks(checks: JsValue) -> CheckOptions {
+    serde_wasm_bindgen::from_value(checks).unwrap_or_default()
+}
+
+// Missing devices fall back to every built in device.
+fn parse_devices(devices: JsValue) -> Vec<DeviceKind> {
+    serde_wasm_bindgen::from_value(devices).unwrap_or_else(|_| DeviceKind::ALL.to_vec())
+}
+
+// Missing or unknown presets fall back to the default layout.
+fn parse_layout(preset: JsValue) -> MemoryLayout {
+    serde_wasm_bindgen::from_value::<LayoutPreset>(preset)
+        .unwrap_or_default()
+        .layout()
+}
+
 #[wasm_bindgen]
 pub struct Runner {
     events: Arc<EventHandler>,
-    display: RefCell<FlushDisplayBody>,
+    displays: RefCell<FlushDisplays>,
     device: RefCell<Option<Rc<dyn RewindableDevice>>>,
 }
 
@@ -101,6 +154,13 @@ impl Runner {
         self.device.borrow().clone()
     }
 
+    // Calls without an id act on the main display.
+    fn find_display(&self, id: Option<String>) -> Option<FlushDisplayBody> {
+        let id = id.unwrap_or_else(|| MAIN_DISPLAY.to_string());
+
+        self.displays.borrow().get(&id).cloned()
+    }
+
     pub fn swap<
         Listen: ListenResponder + Send + 'static,
         Track: Tracker<SectionMemory<Listen>> + Send + 'static,
@@ -108,21 +168,25 @@ impl Runner {
         &self,
         debugger: Executor<SectionMemory<Listen>, Track>,
         finished_pcs: Vec<u32>,
-        keyboard: Arc<Mutex<KeyboardState>>,
+        peripherals: Peripherals,
         console: Box<dyn ConsoleHandler + Send + Sync>,
         midi: Box<dyn MidiHandler + Send + Sync>,
         time: Arc<dyn TimeHandler + Send + Sync>,
+        layout: &MemoryLayout,
+        checker: Option<Checker>,
     ) {
         if let Some(device) = &self.take_device() {
             device.pause()
         }
 
         let wrapped = Arc::new(debugger);
-        let delegate = Arc::new(Mutex::new(SyscallState::new(console, midi, time, None)));
+        let delegate = Arc::new(Mutex::new(SyscallState::new(
+            console, midi, time, None, layout, checker,
+        )));
 
         *self.device.borrow_mut() = Some(Rc::new(ExecutionState {
             debugger: wrapped,
-            keyboard,
+            peripherals,
             delegate,
             finished_pcs,
         }));
@@ -132,21 +196,25 @@ impl Runner {
         &self,
         debugger: Executor<WatchedMemory<Mem>, HistoryTracker>,
         finished_pcs: Vec<u32>,
-        keyboard: Arc<Mutex<KeyboardState>>,
+        peripherals: Peripherals,
         console: Box<dyn ConsoleHandler + Send + Sync>,
         midi: Box<dyn MidiHandler + Send + Sync>,
         time: Arc<dyn TimeHandler + Send + Sync>,
+        layout: &MemoryLayout,
+        checker: Option<Checker>,
     ) {
         if let Some(device) = &self.take_device() {
             device.pause()
         }
 
         let wrapped = Arc::new(debugger);
-        let delegate = Arc::new(Mutex::new(SyscallState::new(console, midi, time, None)));
+        let delegate = Arc::new(Mutex::new(SyscallState::new(
+            console, midi, time, None, layout, checker,
+        )));
 
         *self.device.borrow_mut() = Some(Rc::new(ExecutionState {
             debugger: wrapped,
-            keyboard,
+            peripherals,
             delegate,
             finished_pcs,
         }));
@@ -159,7 +227,7 @@ impl Runner {
     pub fn new(events: EventHandler) -> Runner {
         Runner {
             events: Arc::new(events),
-            display: RefCell::new(Arc::new(Mutex::new(Default::default()))),
+            displays: RefCell::new(single_display(Default::default())),
             device: RefCell::new(None),
         }
     }
@@ -170,24 +238,97 @@ impl Runner {
         }
     }
 
-    pub fn last_display(&self) -> JsValue {
-        let display_borrow = self.display.borrow();
-        let display = display_borrow.lock().unwrap();
+    pub fn last_display(&self, id: Option<String>) -> JsValue {
+        let Some(display) = self.find_display(id) else {
+            return JsValue::NULL;
+        };
+
+        let display = display.lock().unwrap();
 
         serde_wasm_bindgen::to_value(&*display).unwrap()
     }
 
-    pub fn configure_display(&self, address: u32, register: Option<u8>, width: u32, height: u32) {
-        *self.display.borrow_mut() = Arc::new(Mutex::new(FlushDisplayState {
+    pub fn configure_display(
+        &self,
+        id: Option<String>,
+        address: u32,
+        register: Option<u8>,
+        width: u32,
+        height: u32,
+        format: JsValue,
+    ) {
+        let id = id.unwrap_or_else(|| MAIN_DISPLAY.to_string());
+
+        // Keep recording across setting changes.
+        let recording = self
+            .find_display(Some(id.clone()))
+            .and_then(|display| display.lock().unwrap().recording.take());
+
+        let display = Arc::new(Mutex::new(FlushDisplayState {
             address,
             register,
             width,
             height,
+            format: parse_format(format),
+            scale: None,
             data: None,
+            recording,
         }));
+
+        self.displays.borrow_mut().insert(id, display);
     }
 
-    pub fn configure_elf(&self, bytes: Vec<u8>, time_travel: bool) -> bool {
+    pub fn remove_display(&self, id: String) {
+        // Screen and graphics devices draw to the main display, it is never removed.
+        if id == MAIN_DISPLAY {
+            return;
+        }
+
+        self.displays.borrow_mut().remove(&id);
+    }
+
+    pub fn display_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.displays.borrow().keys().cloned().collect();
+
+        ids.sort();
+
+        ids
+    }
+
+    pub fn start_recording(&self, id: Option<String>) {
+        if let Some(display) = self.find_display(id) {
+            display.lock().unwrap().recording = Some(Arc::new(Mutex::new(Recording::new())));
+        }
+    }
+
+    // Stops the recording, returns one file for gifs or one file per frame for png sequences.
+    pub fn take_recording(&self, id: Option<String>, format: JsValue) -> Option<js_sys::Array> {
+        let format: RecordingFormat = serde_wasm_bindgen::from_value(format).ok()?;
+
+        let recording = self.find_display(id)?.lock().unwrap().recording.take()?;
+        let recording = recording.lock().unwrap();
+
+        let files = match format {
+            RecordingFormat::Gif => vec![recording.encode_gif()?],
+            RecordingFormat::PngSequence => recording.encode_png_frames()?,
+        };
+
+        Some(
+            files
+                .iter()
+                .map(|file| js_sys::Uint8Array::from(file.as_slice()))
+                .collect(),
+        )
+    }
+
+    pub fn configure_elf(
+        &self,
+        bytes: Vec<u8>,
+        time_travel: bool,
+        layout: JsValue,
+        checks: JsValue,
+        devices: JsValue,
+    ) -> bool {
         let Ok(elf) = Elf::read(&mut Cursor::new(bytes)) else {
             return false;
         };
@@ -202,42 +343,56 @@ impl Runner {
         });
         let time = Arc::new(WasmTime {});
         let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
+        let layout = parse_layout(layout);
+        let checker = Checker::new(parse_checks(checks), &layout, HashMap::new());
 
         let mut memory = SectionMemory::new();
-        let keyboard = configure_keyboard(&mut memory);
+        let Ok(peripherals) = configure_peripherals(&mut memory, &layout, &parse_devices(devices))
+        else {
+            return false;
+        };
 
         if time_travel {
             let memory = WatchedMemory::new(memory);
 
-            let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-            setup_state(&mut cpu_state);
+            let cpu_state = create_elf_state(&elf, &layout, memory);
 
             self.swap_watched(
                 Executor::new(cpu_state, history),
                 finished_pcs,
-                keyboard,
+                peripherals,
                 console,
                 midi,
                 time,
+                &layout,
+                checker,
             );
         } else {
-            let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-            setup_state(&mut cpu_state);
+            let cpu_state = create_elf_state(&elf, &layout, memory);
 
             self.swap(
                 Executor::new(cpu_state, EmptyTracker {}),
                 finished_pcs,
-                keyboard,
+                peripherals,
                 console,
                 midi,
                 time,
+                &layout,
+                checker,
             );
         }
 
         true
     }
 
-    pub fn configure_asm(&self, text: &str, time_travel: bool) -> JsValue {
+    pub fn configure_asm(
+        &self,
+        text: &str,
+        time_travel: bool,
+        layout: JsValue,
+        checks: JsValue,
+        devices: JsValue,
+    ) -> JsValue {
         let binary = assemble_from(text);
 
         let (binary, result) = AssemblerResult::from_result_with_binary(binary, text);
@@ -256,35 +411,51 @@ impl Runner {
         });
         let time = Arc::new(WasmTime {});
         let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
+        let layout = parse_layout(layout);
+        let checker = Checker::new(parse_checks(checks), &layout, source_lines(&binary, text));
 
         let mut memory = SectionMemory::new();
-        let keyboard = configure_keyboard(&mut memory);
+        let peripherals = match configure_peripherals(&mut memory, &layout, &parse_devices(devices))
+        {
+            Ok(peripherals) => peripherals,
+            Err(message) => {
+                let result = AssemblerResult::Error {
+                    marker: None,
+                    message,
+                    body: None,
+                };
+
+                return serde_wasm_bindgen::to_value(&result).unwrap();
+            }
+        };
 
         if time_travel {
             let memory = WatchedMemory::new(memory);
 
-            let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-            setup_state(&mut cpu_state);
+            let cpu_state = state_from_binary(binary, &layout, memory);
 
             self.swap_watched(
                 Executor::new(cpu_state, history),
                 finished_pcs,
-                keyboard,
+                peripherals,
                 console,
                 midi,
                 time,
+                &layout,
+                checker,
             );
         } else {
-            let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-            setup_state(&mut cpu_state);
+            let cpu_state = state_from_binary(binary, &layout, memory);
 
             self.swap(
                 Executor::new(cpu_state, EmptyTracker {}),
                 finished_pcs,
-                keyboard,
+                peripherals,
                 console,
                 midi,
                 time,
+                &layout,
+                checker,
             );
         }
 
@@ -332,32 +503,150 @@ impl Runner {
         }
     }
 
+    pub fn post_key_code(&self, code: &str, up: bool) {
+        if let Some(device) = &self.take_device() {
+            device.post_key_code(code, up)
+        }
+    }
+
+    pub fn post_mouse(&self, x: i32, y: i32, buttons: u32) {
+        if let Some(device) = &self.take_device() {
+            device.post_mouse(x, y, buttons)
+        }
+    }
+
+    pub fn post_keypad(&self, key: u8, up: bool) {
+        if let Some(device) = &self.take_device() {
+            device.post_keypad(key, up)
+        }
+    }
+
+    pub fn post_switch(&self, index: u8, on: bool) {
+        if let Some(device) = &self.take_device() {
+            device.post_switch(index, on)
+        }
+    }
+
     pub fn wake_sync(&self) {
         if let Some(device) = &self.take_device() {
             device.wake_sync()
         }
     }
 
+    pub fn wake_refresh(&self) {
+        if let Some(device) = &self.take_device() {
+            device.wake_refresh()
+        }
+    }
+
+    // Reads the stored configuration of a display by id.
+    pub fn read_display_id(&self, id: String) -> JsValue {
+        let Some(device) = self.take_device() else {
+            return JsValue::NULL;
+        };
+
+        let main = id == MAIN_DISPLAY;
+
+        let Some(display) = self.find_display(Some(id)) else {
+            return JsValue::NULL;
+        };
+
+        let display = display.lock().unwrap();
+
+        let target = match display.register.and_then(RegisterName::from_u8) {
+            Some(register) => ReadDisplayTarget::Register(register),
+            None => ReadDisplayTarget::Address(display.address),
+        };
+
+        frame_value(device.read_display(
+            target,
+            display.width,
+            display.height,
+            display.format,
+            main,
+        ))
+    }
+
     pub fn read_display(
         &self,
         address: u32,
         register: Option<u8>,
         width: u32,
         height: u32,
-    ) -> Option<Vec<u8>> {
-        if let Some(device) = &self.take_device() {
-            let target = if let Some(register) = register {
-                ReadDisplayTarget::Register(RegisterName::from_u8(register)?)
-            } else {
-                ReadDisplayTarget::Address(address)
-            };
+        format: JsValue,
+    ) -> JsValue {
+        let Some(device) = &self.take_device() else {
+            return JsValue::NULL;
+        };
 
-            device.read_display(target, width, height)
-        } else {
-            None
+        let target = match register {
+            Some(register) => match RegisterName::from_u8(register) {
+                Some(register) => ReadDisplayTarget::Register(register),
+                None => return JsValue::NULL,
+            },
+            None => ReadDisplayTarget::Address(address),
+        };
+
+        frame_value(device.read_display(target, width, height, parse_format(format), true))
+    }
+
+    pub fn read_text(&self) -> JsValue {
+        let Some(device) = &self.take_device() else {
+            return JsValue::NULL;
+        };
+
+        serde_wasm_bindgen::to_value(&device.read_text()).unwrap()
+    }
+
+    pub fn read_lab(&self) -> JsValue {
+        let Some(device) = &self.take_device() else {
+            return JsValue::NULL;
+        };
+
+        serde_wasm_bindgen::to_value(&device.read_lab()).unwrap()
+    }
+
+    pub fn read_devices(&self) -> JsValue {
+        let Some(device) = &self.take_device() else {
+            return JsValue::NULL;
+        };
+
+        // Snapshots are JSON values, objects should come out as plain objects and not maps.
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+
+        device.read_devices().serialize(&serializer).unwrap()
+    }
+
+    pub fn read_heap(&self) -> JsValue {
+        let Some(device) = &self.take_device() else {
+            return JsValue::NULL;
+        };
+
+        // Blocks are flattened, which would otherwise come out as maps.
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+
+        device.read_heap().serialize(&serializer).unwrap()
+    }
+
+    // Oldest buffer the program played as a WAV file.
+    pub fn take_audio(&self) -> Option<Vec<u8>> {
+        Some(self.take_device()?.take_audio()?.encode_wav())
+    }
+
+    pub fn start_audio_capture(&self) {
+        if let Some(device) = &self.take_device() {
+            device.start_audio_capture()
         }
     }
 
+    // Stops the capture, returns everything played since it started as a WAV file.
+    pub fn take_audio_capture(&self) -> Option<Vec<u8>> {
+        let capture = self.take_device()?.take_audio_capture()?;
+        let capture = capture.lock().unwrap();
+
+        capture.encode_wav()
+    }
+
     pub async fn resume(
         &self,
         batch_size: usize,
@@ -369,7 +658,7 @@ impl Runner {
             return JsValue::NULL;
         };
 
-        let display = self.display.borrow().clone();
+        let displays = self.displays.borrow().clone();
 
         let result = device
             .resume(ResumeOptions {
@@ -380,7 +669,7 @@ impl Runner {
                     break_at_end: is_step,
                 }),
                 breakpoints,
-                display: Some(display),
+                displays,
                 change_state: if !is_step && first_batch {
                     Some(ExecutorMode::Running)
                 } else {
@@ -2,6 +2,7 @@ import { MipsBackend } from '../utils/mips/mips'
 import { TauriBackend } from '../utils/mips/tauri-backend'
 import { WasmBackend } from '../utils/mips/wasm-backend'
 import { MidiNote, playNote } from '../utils/midi'
+import { setupAudio } from '../utils/audio'
 import { ConsoleType, pushConsole } from './console-data'
 
 function createBackend(): MipsBackend {
@@ -27,5 +28,7 @@ export async function setupBackend(): Promise<MipsBackend> {
 
   await backend.waitReady()
 
+  setupAudio()
+
   return backend
 }
@@ -6,6 +6,8 @@ export enum DebugTab {
   Memory,
   Console,
   Bitmap,
+  Text,
+  Lab,
   Tests,
   Debug,
 }
@@ -0,0 +1,60 @@
+import { watch } from 'vue'
+import { consoleData } from '../state/console-data'
+
+let context = null as AudioContext | null
+let nextTime = 0
+
+let interval = null as number | null
+let inflight = false
+
+async function playClip(data: ArrayBuffer) {
+  context = context ?? new AudioContext()
+
+  const buffer = await context.decodeAudioData(data)
+  const source = context.createBufferSource()
+
+  source.buffer = buffer
+  source.connect(context.destination)
+
+  // Buffers are queued back to back, so a program streaming audio doesn't click between them.
+  const start = Math.max(nextTime, context.currentTime)
+
+  source.start(start)
+  nextTime = start + buffer.duration
+}
+
+async function pollAudio() {
+  if (inflight) {
+    return
+  }
+
+  inflight = true
+
+  try {
+    let data = await consoleData.execution?.readAudio()
+
+    // Drain everything the program played since the last poll.
+    while (data) {
+      await playClip(data)
+
+      data = await consoleData.execution?.readAudio()
+    }
+  } catch (e) {}
+
+  inflight = false
+}
+
+// Plays PCM buffers from the audio device while a program runs.
+export function setupAudio() {
+  watch(
+    () => consoleData.execution,
+    (execution) => {
+      if (execution && !interval) {
+        interval = window.setInterval(pollAudio, 20)
+      } else if (!execution && interval) {
+        window.clearInterval(interval)
+        interval = null
+      }
+    },
+  )
+}
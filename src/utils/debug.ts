@@ -82,6 +82,14 @@ function postDebugInformation(result: ExecutionResult) {
       break
     }
 
+    case ExecutionModeType.Flagged: {
+      pushConsole(result.mode.message, ConsoleType.Error)
+
+      consoleData.tab = DebugTab.Console
+
+      break
+    }
+
     default:
       break
   }
@@ -196,6 +204,9 @@ export async function resume() {
       path,
       settings.execution.timeTravel,
       current.profile,
+      settings.execution.layout,
+      settings.execution.checks,
+      settings.execution.devices,
     )
   }
 
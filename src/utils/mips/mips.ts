@@ -50,11 +50,60 @@ export interface BinaryResult {
   result: AssemblerResult
 }
 
+export type PixelFormat =
+  | { type: 'rgb888' }
+  | { type: 'argb8888' }
+  | { type: 'rgb565' }
+  | { type: 'palette8'; palette: number }
+  | { type: 'grayscale8' }
+  | { type: 'monochrome1' }
+
+export type RecordingFormat = 'gif' | 'png_sequence'
+
+// Where the stack, $gp, the heap and writable memory go. Segments are not affected.
+export type LayoutPreset = 'saturn' | 'mars_default'
+
+// Warn prints to the console, pause stops before the offending instruction.
+export type CheckMode = 'off' | 'warn' | 'pause'
+
+// Runtime checks, anything on runs the program one instruction at a time.
+export interface CheckOptions {
+  uninitialized: CheckMode // reads of registers or stack/heap never written
+  convention: CheckMode // saved registers, $ra and temporaries across calls
+}
+
+// Built in MMIO devices, a session only maps the ones it is given.
+export type DeviceKind =
+  | 'keyboard'
+  | 'transmitter'
+  | 'lab'
+  | 'timer'
+  | 'scan_code'
+  | 'mouse'
+  | 'screen'
+  | 'text'
+  | 'graphics'
+  | 'audio'
+
+export const ALL_DEVICES: DeviceKind[] = [
+  'keyboard',
+  'transmitter',
+  'lab',
+  'timer',
+  'scan_code',
+  'mouse',
+  'screen',
+  'text',
+  'graphics',
+  'audio',
+]
+
 export interface BitmapConfig {
   width: number
   height: number
   address: number
   register: number | null
+  format?: PixelFormat
 }
 
 export enum ExecutionModeType {
@@ -64,6 +113,7 @@ export enum ExecutionModeType {
   Stopped = 'Stopped',
   Breakpoint = 'Breakpoint',
   Finished = 'Finished',
+  Flagged = 'Flagged',
 }
 
 export interface ExecutionModeInvalid {
@@ -71,6 +121,12 @@ export interface ExecutionModeInvalid {
   message: string
 }
 
+// Paused by a runtime check before the instruction at pc, resuming runs it.
+export interface ExecutionModeFlagged {
+  type: ExecutionModeType.Flagged
+  message: string
+}
+
 export interface ExecutionModeFinished {
   type: ExecutionModeType.Finished
   pc: number
@@ -84,14 +140,23 @@ type ExecutionModeOther =
 
 export type ExecutionMode =
   | ExecutionModeInvalid
+  | ExecutionModeFlagged
   | ExecutionModeFinished
   | { type: ExecutionModeOther }
 
+export interface FloatingRegisters {
+  raw: number[]
+  single: (number | null)[]
+  double: (number | null)[]
+  condition: number
+}
+
 export interface Registers {
   pc: number
   line: number[]
   lo: number
   hi: number
+  fp: FloatingRegisters
 }
 
 export interface ExecutionResult {
@@ -99,13 +164,68 @@ export interface ExecutionResult {
   registers: Registers
 }
 
+// Id of the display the program's screen and graphics devices draw to.
+export const MAIN_DISPLAY = 'main'
+
 export interface LastDisplay {
   address: number
   width: number
   height: number
+  format: PixelFormat
+  scale: number | null
   data: number[] | null
 }
 
+export interface DisplayFrame {
+  width: number
+  height: number
+  scale: number | null // host pixels per display pixel, if the program asked for one
+  data: Uint8Array
+}
+
+// Digital lab panel, bits are set for lit segments and LEDs, switches that are on and held keys.
+export interface LabDisplay {
+  left: number // segments a to g in bits 0 to 6, the dot in bit 7
+  right: number
+  leds: number
+  switches: number
+  keys: number // keypad key n is bit n
+}
+
+// UI state of a registered MMIO device, the shape of state depends on the device.
+export interface DeviceSnapshot {
+  name: string
+  state: unknown
+}
+
+export interface HeapBlock {
+  address: number
+  size: number // after alignment
+  requested: number
+  pc: number // the sbrk syscall
+  preview: (number | null)[] // first bytes, null if unmapped
+  pointers: { offset: number; target: number }[] // words pointing at other blocks
+}
+
+export interface HeapReport {
+  start: number
+  limit: number
+  current: number // the program break
+  high_water: number
+  stack_pointer: number
+  stack_low_water: number
+  closest: number // bytes between the highest break and the lowest $sp
+  approaching: boolean // the stack and heap are getting close
+  blocks: HeapBlock[]
+}
+
+export interface TextDisplay {
+  columns: number
+  rows: number
+  cursor: [number, number] | null // column, row
+  cells: number[] // character, attribute pairs in row order
+}
+
 export class Breakpoints {
   public maxLine: number
   public lineToPc: Map<number, number[]>
@@ -255,16 +375,27 @@ export interface MipsBackend {
   ): Promise<HexBinaryResult>
 
   // Execution
-  configureDisplay(config: BitmapConfig): Promise<void>
-  lastDisplay(): Promise<LastDisplay>
+  // Displays are named, calls without an id act on MAIN_DISPLAY.
+  configureDisplay(config: BitmapConfig, id?: string): Promise<void>
+  lastDisplay(id?: string): Promise<LastDisplay | null>
+  removeDisplay(id: string): Promise<void>
+  displayIds(): Promise<string[]>
 
   wakeSync(): Promise<void>
+  wakeRefresh(): Promise<void>
+
+  // Display recordings, saving stops the recording.
+  startRecording(id?: string): Promise<void>
+  saveRecording(format: RecordingFormat, id?: string): Promise<void>
 
   createExecution(
     text: string,
     path: string | null,
     timeTravel: boolean,
     profile: ExecutionProfile,
+    layout?: LayoutPreset,
+    checks?: CheckOptions,
+    devices?: DeviceKind[],
   ): Promise<MipsExecution>
 
   close(): void
@@ -288,6 +419,12 @@ export interface MipsExecution {
   setBreakpoints(breakpoints: number[]): Promise<void>
 
   postKey(key: string, up: boolean): Promise<void>
+  // Code is a KeyboardEvent.code, like 'ArrowUp', for the scan code keyboard.
+  postKeyCode(code: string, up: boolean): Promise<void>
+  // Position is in display pixels, buttons is a MouseEvent.buttons mask.
+  postMouse(x: number, y: number, buttons: number): Promise<void>
+  postKeypad(key: number, up: boolean): Promise<void>
+  postSwitch(index: number, on: boolean): Promise<void>
   postInput(text: string): Promise<void>
 
   memoryAt(address: number, count: number): Promise<(number | null)[] | null>
@@ -295,10 +432,30 @@ export interface MipsExecution {
   setMemory(address: number, bytes: number[]): Promise<void>
 
   // Live display, should generally be more performant on tauri.
+  // The frame size can differ from the requested one if the program configured the display.
   readDisplay(
     width: number,
     height: number,
     address: number,
     register: number | null,
-  ): Promise<Uint8Array | null>
+    format?: PixelFormat,
+  ): Promise<DisplayFrame | null>
+  // Uses the configuration stored for the display.
+  readDisplayById(id: string): Promise<DisplayFrame | null>
+
+  // Character cells of the text display.
+  readText(): Promise<TextDisplay | null>
+
+  // Seven-segment displays, LEDs, switches and keypad.
+  readLab(): Promise<LabDisplay | null>
+  // Every registered device that has something to show.
+  readDevices(): Promise<DeviceSnapshot[] | null>
+  // Every sbrk allocation, high-water marks and stack/heap distance.
+  readHeap(): Promise<HeapReport | null>
+
+  // Oldest PCM buffer the program played as a WAV file, null if there is nothing new.
+  readAudio(): Promise<ArrayBuffer | null>
+  // Everything played until saving is captured to one WAV file.
+  startAudioCapture(): Promise<void>
+  saveAudioCapture(): Promise<void>
 }
@@ -5,16 +5,26 @@ import {
   BitmapConfig,
   Breakpoint,
   Breakpoints,
+  CheckOptions,
+  DeviceKind,
+  DeviceSnapshot,
   DisassembleResult,
+  DisplayFrame,
   ExecutionProfile,
   ExecutionResult,
+  HeapReport,
   HexBinaryResult,
   InstructionDetails,
   InstructionLine,
+  LabDisplay,
   LastDisplay,
+  LayoutPreset,
   MipsBackend,
   MipsCallbacks,
   MipsExecution,
+  PixelFormat,
+  RecordingFormat,
+  TextDisplay,
 } from './mips'
 import { ExportRegionsOptions } from '../settings'
 
@@ -22,11 +32,20 @@ import { convertFileSrc, invoke } from '@tauri-apps/api/core'
 import { listen } from '@tauri-apps/api/event'
 import { MidiNote } from '../midi'
 
+// Only sent when the program picked a scale for the display.
+function scaleHeader(headers: Headers): number | null {
+  const scale = headers.get('scale')
+
+  return scale ? Number(scale) : null
+}
+
 export class TauriExecution implements MipsExecution {
   configured: boolean = false
   public breakpoints: Breakpoints | null
 
   protocol = convertFileSrc('', 'display')
+  textProtocol = convertFileSrc('', 'text')
+  audioProtocol = convertFileSrc('', 'audio')
 
   async configure(): Promise<AssemblerResult | null> {
     if (this.configured) {
@@ -48,6 +67,9 @@ export class TauriExecution implements MipsExecution {
           bytes,
           path: this.path,
           timeTravel: this.timeTravel,
+          layout: this.layout,
+          checks: this.checks,
+          devices: this.devices,
         })
 
         return result
@@ -65,6 +87,9 @@ export class TauriExecution implements MipsExecution {
           text: this.text,
           path: this.path,
           timeTravel: this.timeTravel,
+          layout: this.layout,
+          checks: this.checks,
+          devices: this.devices,
         })) as AssemblerResult
 
         if (result.status === 'Success') {
@@ -141,6 +166,22 @@ export class TauriExecution implements MipsExecution {
     await invoke('post_key', { key, up })
   }
 
+  public async postKeyCode(code: string, up: boolean) {
+    await invoke('post_key_code', { code, up })
+  }
+
+  public async postMouse(x: number, y: number, buttons: number) {
+    await invoke('post_mouse', { x, y, buttons })
+  }
+
+  public async postKeypad(key: number, up: boolean) {
+    await invoke('post_keypad', { key, up })
+  }
+
+  public async postSwitch(index: number, on: boolean) {
+    await invoke('post_switch', { index, on })
+  }
+
   public async postInput(text: string) {
     if (text.length <= 0) {
       return
@@ -172,12 +213,17 @@ export class TauriExecution implements MipsExecution {
     height: number,
     address: number,
     register: number | null,
-  ): Promise<Uint8Array | null> {
+    format?: PixelFormat,
+  ): Promise<DisplayFrame | null> {
     const headers = {
       width: width.toString(),
       height: height.toString(),
       address: address.toString(),
       ...(register != null ? { register: register.toString() } : {}),
+      ...(format ? { format: format.type } : {}),
+      ...(format?.type === 'palette8'
+        ? { palette: format.palette.toString() }
+        : {}),
     }
 
     const result = await fetch(this.protocol, {
@@ -186,7 +232,85 @@ export class TauriExecution implements MipsExecution {
       cache: 'no-cache',
     })
 
-    return new Uint8Array(await result.arrayBuffer())
+    return {
+      width: Number(result.headers.get('width') ?? width),
+      height: Number(result.headers.get('height') ?? height),
+      scale: scaleHeader(result.headers),
+      data: new Uint8Array(await result.arrayBuffer()),
+    }
+  }
+
+  async readDisplayById(id: string): Promise<DisplayFrame | null> {
+    const result = await fetch(this.protocol, {
+      headers: { id },
+      mode: 'cors',
+      cache: 'no-cache',
+    })
+
+    if (!result.ok) {
+      return null
+    }
+
+    return {
+      width: Number(result.headers.get('width')),
+      height: Number(result.headers.get('height')),
+      scale: scaleHeader(result.headers),
+      data: new Uint8Array(await result.arrayBuffer()),
+    }
+  }
+
+  async readLab(): Promise<LabDisplay | null> {
+    return await invoke('read_lab')
+  }
+
+  async readDevices(): Promise<DeviceSnapshot[] | null> {
+    return await invoke('read_devices')
+  }
+
+  async readHeap(): Promise<HeapReport | null> {
+    return await invoke('read_heap')
+  }
+
+  async readAudio(): Promise<ArrayBuffer | null> {
+    const result = await fetch(this.audioProtocol, {
+      mode: 'cors',
+      cache: 'no-cache',
+    })
+
+    // 204 when the program has not played anything new.
+    if (result.status !== 200) {
+      return null
+    }
+
+    return await result.arrayBuffer()
+  }
+
+  async startAudioCapture(): Promise<void> {
+    await invoke('start_audio_capture')
+  }
+
+  async saveAudioCapture(): Promise<void> {
+    await invoke('save_audio_capture')
+  }
+
+  async readText(): Promise<TextDisplay | null> {
+    const result = await fetch(this.textProtocol, {
+      mode: 'cors',
+      cache: 'no-cache',
+    })
+
+    if (!result.ok) {
+      return null
+    }
+
+    const cursor = result.headers.get('cursor')?.split(',').map(Number)
+
+    return {
+      columns: Number(result.headers.get('columns')),
+      rows: Number(result.headers.get('rows')),
+      cursor: cursor ? [cursor[0], cursor[1]] : null,
+      cells: Array.from(new Uint8Array(await result.arrayBuffer())),
+    }
   }
 
   public constructor(
@@ -194,6 +318,9 @@ export class TauriExecution implements MipsExecution {
     public path: string | null,
     public timeTravel: boolean,
     public profile: ExecutionProfile,
+    public layout?: LayoutPreset,
+    public checks?: CheckOptions,
+    public devices?: DeviceKind[],
   ) {
     switch (profile.kind) {
       case 'elf': {
@@ -294,12 +421,14 @@ export class TauriBackend implements MipsBackend {
     }
   }
 
-  async configureDisplay(config: BitmapConfig): Promise<void> {
+  async configureDisplay(config: BitmapConfig, id?: string): Promise<void> {
     await invoke('configure_display', {
+      id,
       width: config.width,
       height: config.height,
       address: config.address,
       register: config.register,
+      format: config.format,
     })
   }
 
@@ -327,23 +456,56 @@ export class TauriBackend implements MipsBackend {
     })
   }
 
-  async lastDisplay(): Promise<LastDisplay> {
-    const result = await invoke('last_display')
+  async lastDisplay(id?: string): Promise<LastDisplay | null> {
+    const result = await invoke('last_display', { id })
+
+    return result as LastDisplay | null
+  }
+
+  async removeDisplay(id: string): Promise<void> {
+    await invoke('remove_display', { id })
+  }
 
-    return result as LastDisplay
+  async displayIds(): Promise<string[]> {
+    return await invoke('display_ids')
   }
 
   async wakeSync(): Promise<void> {
     await invoke('wake_sync')
   }
 
+  async wakeRefresh(): Promise<void> {
+    await invoke('wake_refresh')
+  }
+
+  async startRecording(id?: string): Promise<void> {
+    await invoke('start_recording', { id })
+  }
+
+  async saveRecording(format: RecordingFormat, id?: string): Promise<void> {
+    await invoke('save_recording', { id, format })
+  }
+
   createExecution(
     text: string,
     path: string | null,
     timeTravel: boolean,
     profile: ExecutionProfile,
+    layout?: LayoutPreset,
+    checks?: CheckOptions,
+    devices?: DeviceKind[],
   ): Promise<MipsExecution> {
-    return Promise.resolve(new TauriExecution(text, path, timeTravel, profile))
+    return Promise.resolve(
+      new TauriExecution(
+        text,
+        path,
+        timeTravel,
+        profile,
+        layout,
+        checks,
+        devices,
+      ),
+    )
   }
 
   close() {
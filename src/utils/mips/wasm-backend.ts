@@ -3,16 +3,26 @@ import {
   BinaryResult,
   BitmapConfig,
   Breakpoints,
+  CheckOptions,
+  DeviceKind,
+  DeviceSnapshot,
   DisassembleResult,
+  DisplayFrame,
   ExecutionProfile,
   ExecutionResult,
+  HeapReport,
   HexBinaryResult,
   InstructionDetails,
   InstructionLine,
+  LabDisplay,
   LastDisplay,
+  LayoutPreset,
   MipsBackend,
   MipsCallbacks,
   MipsExecution,
+  PixelFormat,
+  RecordingFormat,
+  TextDisplay,
 } from './mips'
 import WasmWorker from './wasm-worker?worker'
 import { ExportRegionsOptions } from '../settings'
@@ -200,12 +210,23 @@ export class WasmBackend implements MipsBackend {
     })
   }
 
-  async lastDisplay(): Promise<LastDisplay> {
-    return await this.sendRequest<LastDisplay>({ op: MessageOp.LastDisplay })
+  async lastDisplay(id?: string): Promise<LastDisplay | null> {
+    return await this.sendRequest<LastDisplay | null>({
+      op: MessageOp.LastDisplay,
+      id,
+    })
+  }
+
+  async configureDisplay(config: BitmapConfig, id?: string): Promise<void> {
+    await this.sendRequest({ op: MessageOp.ConfigureDisplay, config, id })
+  }
+
+  async removeDisplay(id: string): Promise<void> {
+    await this.sendRequest({ op: MessageOp.RemoveDisplay, id })
   }
 
-  async configureDisplay(config: BitmapConfig): Promise<void> {
-    await this.sendRequest({ op: MessageOp.ConfigureDisplay, config })
+  async displayIds(): Promise<string[]> {
+    return await this.sendRequest<string[]>({ op: MessageOp.DisplayIds })
   }
 
   wakeSync(): Promise<void> {
@@ -214,13 +235,65 @@ export class WasmBackend implements MipsBackend {
     })
   }
 
+  wakeRefresh(): Promise<void> {
+    return this.sendRequest({
+      op: MessageOp.WakeRefresh,
+    })
+  }
+
+  startRecording(id?: string): Promise<void> {
+    return this.sendRequest({
+      op: MessageOp.StartRecording,
+      id,
+    })
+  }
+
+  async saveRecording(format: RecordingFormat, id?: string): Promise<void> {
+    const files = await this.sendRequest<Uint8Array[] | null>({
+      op: MessageOp.TakeRecording,
+      id,
+      format,
+    })
+
+    if (!files) {
+      return
+    }
+
+    // No file system access here, download every file instead.
+    files.forEach((file, index) => {
+      const name =
+        format === 'gif'
+          ? 'recording.gif'
+          : `recording_${(index + 1).toString().padStart(4, '0')}.png`
+
+      const link = document.createElement('a')
+      link.href = URL.createObjectURL(new Blob([file]))
+      link.download = name
+      link.click()
+
+      URL.revokeObjectURL(link.href)
+    })
+  }
+
   async createExecution(
     text: string,
     path: string | null,
     timeTravel: boolean,
     profile: ExecutionProfile,
+    layout?: LayoutPreset,
+    checks?: CheckOptions,
+    devices?: DeviceKind[],
   ): Promise<MipsExecution> {
-    return new WasmExecution(this, text, path, timeTravel, profile)
+    return new WasmExecution(
+      this,
+      text,
+      path,
+      timeTravel,
+      profile,
+      layout,
+      checks,
+      devices,
+    )
   }
 
   constructor() {
@@ -258,6 +331,9 @@ export class WasmExecution implements MipsExecution {
           op: MessageOp.ConfigureElf,
           bytes,
           timeTravel: this.timeTravel,
+          layout: this.layout,
+          checks: this.checks,
+          devices: this.devices,
         })
 
         return result
@@ -275,6 +351,9 @@ export class WasmExecution implements MipsExecution {
           op: MessageOp.ConfigureAsm,
           text: this.text,
           timeTravel: this.timeTravel,
+          layout: this.layout,
+          checks: this.checks,
+          devices: this.devices,
         })
 
         if (result.status === 'Success') {
@@ -341,6 +420,39 @@ export class WasmExecution implements MipsExecution {
     })
   }
 
+  postKeyCode(code: string, up: boolean): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.PostKeyCode,
+      code,
+      up,
+    })
+  }
+
+  postMouse(x: number, y: number, buttons: number): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.PostMouse,
+      x,
+      y,
+      buttons,
+    })
+  }
+
+  postKeypad(key: number, up: boolean): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.PostKeypad,
+      key,
+      up,
+    })
+  }
+
+  postSwitch(index: number, on: boolean): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.PostSwitch,
+      index,
+      on,
+    })
+  }
+
   pause(): Promise<void> {
     return this.backend.sendRequest({ op: MessageOp.Pause })
   }
@@ -376,21 +488,89 @@ export class WasmExecution implements MipsExecution {
     height: number,
     address: number,
     register: number | null,
-  ): Promise<Uint8Array | null> {
-    return this.backend.sendRequest<Uint8Array | null>({
+    format?: PixelFormat,
+  ): Promise<DisplayFrame | null> {
+    return this.backend.sendRequest<DisplayFrame | null>({
       op: MessageOp.ReadDisplay,
       width,
       height,
       address,
       register,
+      format,
     })
   }
 
+  readDisplayById(id: string): Promise<DisplayFrame | null> {
+    return this.backend.sendRequest<DisplayFrame | null>({
+      op: MessageOp.ReadDisplayById,
+      id,
+    })
+  }
+
+  readText(): Promise<TextDisplay | null> {
+    return this.backend.sendRequest<TextDisplay | null>({
+      op: MessageOp.ReadText,
+    })
+  }
+
+  readLab(): Promise<LabDisplay | null> {
+    return this.backend.sendRequest<LabDisplay | null>({
+      op: MessageOp.ReadLab,
+    })
+  }
+
+  readDevices(): Promise<DeviceSnapshot[] | null> {
+    return this.backend.sendRequest<DeviceSnapshot[] | null>({
+      op: MessageOp.ReadDevices,
+    })
+  }
+
+  readHeap(): Promise<HeapReport | null> {
+    return this.backend.sendRequest<HeapReport | null>({
+      op: MessageOp.ReadHeap,
+    })
+  }
+
+  async readAudio(): Promise<ArrayBuffer | null> {
+    const data = await this.backend.sendRequest<Uint8Array | null>({
+      op: MessageOp.TakeAudio,
+    })
+
+    return data ? new Uint8Array(data).buffer : null
+  }
+
+  startAudioCapture(): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.StartAudioCapture,
+    })
+  }
+
+  async saveAudioCapture(): Promise<void> {
+    const file = await this.backend.sendRequest<Uint8Array | null>({
+      op: MessageOp.TakeAudioCapture,
+    })
+
+    if (!file) {
+      return
+    }
+
+    // No file system access here, download the file instead.
+    const link = document.createElement('a')
+    link.href = URL.createObjectURL(new Blob([file], { type: 'audio/wav' }))
+    link.download = 'audio.wav'
+    link.click()
+
+    URL.revokeObjectURL(link.href)
+  }
+
   constructor(
     public backend: WasmBackend,
     public text: string,
     public path: string | null,
     public timeTravel: boolean,
     public profile: ExecutionProfile,
+    public layout?: LayoutPreset,
+    public checks?: CheckOptions,
+    public devices?: DeviceKind[],
   ) {}
 }
@@ -1,5 +1,12 @@
 import { type ExportRegionsOptions } from '../settings'
-import { type BitmapConfig } from './mips'
+import {
+  type BitmapConfig,
+  type CheckOptions,
+  type DeviceKind,
+  type LayoutPreset,
+  type PixelFormat,
+  type RecordingFormat,
+} from './mips'
 import { type MidiNote } from '../midi'
 
 export enum MessageOp {
@@ -24,8 +31,25 @@ export enum MessageOp {
   PostInput,
   PostKey,
   WakeSync,
+  WakeRefresh,
   Rewind,
   ReadDisplay,
+  ReadText,
+  StartRecording,
+  TakeRecording,
+  RemoveDisplay,
+  DisplayIds,
+  ReadDisplayById,
+  PostKeypad,
+  PostSwitch,
+  ReadLab,
+  TakeAudio,
+  StartAudioCapture,
+  TakeAudioCapture,
+  ReadDevices,
+  ReadHeap,
+  PostKeyCode,
+  PostMouse,
 }
 
 export interface AssembleRegionsData {
@@ -74,10 +98,21 @@ export interface ConfigureDisplayData {
   op: MessageOp.ConfigureDisplay
 
   config: BitmapConfig
+  id?: string
 }
 
 export interface LastDisplayData {
   op: MessageOp.LastDisplay
+  id?: string
+}
+
+export interface RemoveDisplayData {
+  op: MessageOp.RemoveDisplay
+  id: string
+}
+
+export interface DisplayIdsData {
+  op: MessageOp.DisplayIds
 }
 
 export interface ConfigureElfData {
@@ -85,6 +120,9 @@ export interface ConfigureElfData {
 
   bytes: Uint8Array
   timeTravel: boolean
+  layout?: LayoutPreset
+  checks?: CheckOptions
+  devices?: DeviceKind[]
 }
 
 export interface ConfigureAsmData {
@@ -92,6 +130,9 @@ export interface ConfigureAsmData {
 
   text: string
   timeTravel: boolean
+  layout?: LayoutPreset
+  checks?: CheckOptions
+  devices?: DeviceKind[]
 }
 
 export interface ResumeData {
@@ -147,10 +188,63 @@ export interface PostKeyData {
   up: boolean
 }
 
+export interface PostKeyCodeData {
+  op: MessageOp.PostKeyCode
+  code: string
+  up: boolean
+}
+
+export interface PostMouseData {
+  op: MessageOp.PostMouse
+  x: number
+  y: number
+  buttons: number
+}
+
+export interface PostKeypadData {
+  op: MessageOp.PostKeypad
+  key: number
+  up: boolean
+}
+
+export interface PostSwitchData {
+  op: MessageOp.PostSwitch
+  index: number
+  on: boolean
+}
+
+export interface ReadLabData {
+  op: MessageOp.ReadLab
+}
+
+export interface TakeAudioData {
+  op: MessageOp.TakeAudio
+}
+
+export interface StartAudioCaptureData {
+  op: MessageOp.StartAudioCapture
+}
+
+export interface TakeAudioCaptureData {
+  op: MessageOp.TakeAudioCapture
+}
+
+export interface ReadDevicesData {
+  op: MessageOp.ReadDevices
+}
+
+export interface ReadHeapData {
+  op: MessageOp.ReadHeap
+}
+
 export interface WakeSyncData {
   op: MessageOp.WakeSync
 }
 
+export interface WakeRefreshData {
+  op: MessageOp.WakeRefresh
+}
+
 export interface RewindData {
   op: MessageOp.Rewind
   count: number
@@ -162,6 +256,27 @@ export interface ReadDisplayData {
   height: number
   address: number
   register: number | null
+  format?: PixelFormat
+}
+
+export interface ReadDisplayByIdData {
+  op: MessageOp.ReadDisplayById
+  id: string
+}
+
+export interface ReadTextData {
+  op: MessageOp.ReadText
+}
+
+export interface StartRecordingData {
+  op: MessageOp.StartRecording
+  id?: string
+}
+
+export interface TakeRecordingData {
+  op: MessageOp.TakeRecording
+  id?: string
+  format: RecordingFormat
 }
 
 export type MessageData =
@@ -186,8 +301,25 @@ export type MessageData =
   | PostInputData
   | PostKeyData
   | WakeSyncData
+  | WakeRefreshData
   | RewindData
   | ReadDisplayData
+  | ReadTextData
+  | StartRecordingData
+  | TakeRecordingData
+  | RemoveDisplayData
+  | DisplayIdsData
+  | ReadDisplayByIdData
+  | PostKeypadData
+  | PostSwitchData
+  | ReadLabData
+  | TakeAudioData
+  | StartAudioCaptureData
+  | TakeAudioCaptureData
+  | ReadDevicesData
+  | ReadHeapData
+  | PostKeyCodeData
+  | PostMouseData
 
 export enum MessageEventOp {
   ConsoleWrite,
@@ -4,12 +4,17 @@ import {
   type AssembledRegions,
   type AssemblerResult,
   type BinaryResult,
+  type DeviceSnapshot,
   type DisassembleResult,
+  type DisplayFrame,
   type ExecutionResult,
+  type HeapReport,
   type HexBinaryResult,
   type InstructionDetails,
   type InstructionLine,
+  type LabDisplay,
   type LastDisplay,
+  type TextDisplay,
 } from './mips'
 import {
   AssembleBinaryData,
@@ -21,6 +26,7 @@ import {
   DecodeInstructionData,
   DetailedDisassembleData,
   DisassembleData,
+  LastDisplayData,
   Message,
   MessageData,
   MessageEventData,
@@ -30,12 +36,20 @@ import {
   MessageResponseKind,
   PostInputData,
   PostKeyData,
+  PostKeyCodeData,
+  PostMouseData,
+  PostKeypadData,
+  PostSwitchData,
   ReadBytesData,
+  ReadDisplayByIdData,
   ReadDisplayData,
+  RemoveDisplayData,
   ResumeData,
   RewindData,
   SetBreakpointsData,
   SetRegisterData,
+  StartRecordingData,
+  TakeRecordingData,
   WriteBytesData,
 } from './wasm-worker-message'
 import { type MidiNote } from '../midi'
@@ -103,25 +117,47 @@ function detailedDisassemble({
   return backend.detailed_disassemble(bytes) as InstructionLine[]
 }
 
-function configureDisplay({ config }: ConfigureDisplayData) {
+function configureDisplay({ config, id }: ConfigureDisplayData) {
   runner.configure_display(
+    id,
     config.address,
     config.register ?? undefined,
     config.width,
     config.height,
+    config.format,
   )
 }
 
-function lastDisplay(): LastDisplay {
-  return runner.last_display()
+function lastDisplay({ id }: LastDisplayData): LastDisplay | null {
+  return runner.last_display(id)
 }
 
-function configureElf({ bytes, timeTravel }: ConfigureElfData): boolean {
-  return runner.configure_elf(bytes, timeTravel)
+function removeDisplay({ id }: RemoveDisplayData) {
+  runner.remove_display(id)
 }
 
-function configureAsm({ text, timeTravel }: ConfigureAsmData): AssemblerResult {
-  return runner.configure_asm(text, timeTravel)
+function displayIds(): string[] {
+  return runner.display_ids()
+}
+
+function configureElf({
+  bytes,
+  timeTravel,
+  layout,
+  checks,
+  devices,
+}: ConfigureElfData): boolean {
+  return runner.configure_elf(bytes, timeTravel, layout, checks, devices)
+}
+
+function configureAsm({
+  text,
+  timeTravel,
+  layout,
+  checks,
+  devices,
+}: ConfigureAsmData): AssemblerResult {
+  return runner.configure_asm(text, timeTravel, layout, checks, devices)
 }
 
 // Thanks to Milo
@@ -222,16 +258,89 @@ function postKey({ key, up }: PostKeyData) {
   runner.post_key(key, up)
 }
 
+function postKeyCode({ code, up }: PostKeyCodeData) {
+  runner.post_key_code(code, up)
+}
+
+function postMouse({ x, y, buttons }: PostMouseData) {
+  runner.post_mouse(x, y, buttons)
+}
+
+function postKeypad({ key, up }: PostKeypadData) {
+  runner.post_keypad(key, up)
+}
+
+function postSwitch({ index, on }: PostSwitchData) {
+  runner.post_switch(index, on)
+}
+
 function wakeSync() {
   runner.wake_sync()
 }
 
+function wakeRefresh() {
+  runner.wake_refresh()
+}
+
 function rewind({ count }: RewindData): ExecutionResult | null {
   return runner.rewind(count)
 }
 
-function readDisplay({ width, height, address, register }: ReadDisplayData) {
-  return runner.read_display(address, register ?? undefined, width, height)
+function readDisplay({
+  width,
+  height,
+  address,
+  register,
+  format,
+}: ReadDisplayData): DisplayFrame | null {
+  // The size comes with the data, the program may have picked its own resolution.
+  return runner.read_display(
+    address,
+    register ?? undefined,
+    width,
+    height,
+    format,
+  )
+}
+
+function readDisplayById({ id }: ReadDisplayByIdData): DisplayFrame | null {
+  return runner.read_display_id(id)
+}
+
+function readLab(): LabDisplay | null {
+  return runner.read_lab()
+}
+
+function readDevices(): DeviceSnapshot[] | null {
+  return runner.read_devices()
+}
+
+function readHeap(): HeapReport | null {
+  return runner.read_heap()
+}
+
+function takeAudio(): Uint8Array | null {
+  return runner.take_audio() ?? null
+}
+
+function startAudioCapture() {
+  runner.start_audio_capture()
+}
+
+function takeAudioCapture(): Uint8Array | null {
+  return runner.take_audio_capture() ?? null
+}
+
+function readText(): TextDisplay | null {
+  return runner.read_text()
+}
+
+function startRecording({ id }: StartRecordingData) {
+  runner.start_recording(id)
+}
+
+function takeRecording({ id, format }: TakeRecordingData): Uint8Array[] | null {
+  return runner.take_recording(id, format) ?? null
 }
 
 async function dispatchOp(data: MessageData): Promise<any> {
@@ -251,7 +360,7 @@ async function dispatchOp(data: MessageData): Promise<any> {
     case MessageOp.ConfigureDisplay:
       return configureDisplay(data)
     case MessageOp.LastDisplay:
-      return lastDisplay()
+      return lastDisplay(data)
     case MessageOp.ConfigureElf:
       return configureElf(data)
     case MessageOp.ConfigureAsm:
@@ -278,10 +387,44 @@ async function dispatchOp(data: MessageData): Promise<any> {
       return postKey(data)
     case MessageOp.WakeSync:
       return wakeSync()
+    case MessageOp.WakeRefresh:
+      return wakeRefresh()
     case MessageOp.Rewind:
       return rewind(data)
     case MessageOp.ReadDisplay:
       return readDisplay(data)
+    case MessageOp.ReadText:
+      return readText()
+    case MessageOp.StartRecording:
+      return startRecording(data)
+    case MessageOp.TakeRecording:
+      return takeRecording(data)
+    case MessageOp.RemoveDisplay:
+      return removeDisplay(data)
+    case MessageOp.DisplayIds:
+      return displayIds()
+    case MessageOp.ReadDisplayById:
+      return readDisplayById(data)
+    case MessageOp.PostKeypad:
+      return postKeypad(data)
+    case MessageOp.PostSwitch:
+      return postSwitch(data)
+    case MessageOp.ReadLab:
+      return readLab()
+    case MessageOp.TakeAudio:
+      return takeAudio()
+    case MessageOp.StartAudioCapture:
+      return startAudioCapture()
+    case MessageOp.TakeAudioCapture:
+      return takeAudioCapture()
+    case MessageOp.ReadDevices:
+      return readDevices()
+    case MessageOp.ReadHeap:
+      return readHeap()
+    case MessageOp.PostKeyCode:
+      return postKeyCode(data)
+    case MessageOp.PostMouse:
+      return postMouse(data)
   }
 }
 
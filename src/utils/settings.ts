@@ -1,5 +1,12 @@
 import { reactive, watch } from 'vue'
-import { BitmapConfig } from './mips/mips'
+import {
+  ALL_DEVICES,
+  BitmapConfig,
+  CheckOptions,
+  DeviceKind,
+  LayoutPreset,
+  PixelFormat,
+} from './mips/mips'
 import { backend } from '../state/backend'
 
 const settingsVersion = 6
@@ -17,6 +24,7 @@ export interface BitmapSettings {
   unitHeight: number
   address: number
   register?: number | undefined // new property - dont reset settings for people
+  format?: PixelFormat | undefined // new property - dont reset settings for people
 }
 
 export interface EditorSettings {
@@ -41,6 +49,9 @@ export interface RegisterSettings {
 
 export interface ExecutionSettings {
   timeTravel: boolean
+  layout: LayoutPreset
+  checks: CheckOptions
+  devices: DeviceKind[] // mapped when a program runs
 }
 
 export enum AddressingMode {
@@ -89,6 +100,12 @@ function defaultSettings(): Settings {
     },
     execution: {
       timeTravel: true,
+      layout: 'saturn',
+      checks: {
+        uninitialized: 'off',
+        convention: 'off',
+      },
+      devices: [...ALL_DEVICES],
     },
     memory: {
       address: '0x10010000',
@@ -116,6 +133,22 @@ function fromStorage(): Settings {
       if (object.editor.showMinimap === undefined) {
         object.editor.showMinimap = true
       }
+      if (object.execution.layout === undefined) {
+        object.execution.layout = 'saturn'
+      }
+      // The compact MARS and SPIM presets were dropped, they did not move the segments.
+      if (!['saturn', 'mars_default'].includes(object.execution.layout)) {
+        object.execution.layout = 'mars_default'
+      }
+      if (object.execution.checks === undefined) {
+        object.execution.checks = { uninitialized: 'off', convention: 'off' }
+      }
+      if (object.execution.checks.convention === undefined) {
+        object.execution.checks.convention = 'off'
+      }
+      if (object.execution.devices === undefined) {
+        object.execution.devices = [...ALL_DEVICES]
+      }
       return object
     }
   }
@@ -135,6 +168,7 @@ export function displayConfig(bitmap: BitmapSettings): BitmapConfig {
     height: Math.ceil(bitmap.displayHeight / bitmap.unitHeight),
     address: bitmap.address,
     register: bitmap.register ?? null,
+    format: bitmap.format,
   }
 }
 
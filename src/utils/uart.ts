@@ -0,0 +1,29 @@
+import { invoke } from '@tauri-apps/api/core'
+
+// Host end of the UART device, only the desktop app can bridge it.
+export interface UartStatus {
+  address: string | null // host:port for TCP, the device path for a PTY
+  connected: boolean
+}
+
+export function uartSupported(): boolean {
+  return !!window.__TAURI_INTERNALS__
+}
+
+// Port 0 picks any free port, resolves to the address to connect to.
+export async function uartListen(port: number): Promise<string> {
+  return await invoke('uart_listen', { port })
+}
+
+// Resolves to the terminal device path, for screen or minicom.
+export async function uartOpenPty(): Promise<string> {
+  return await invoke('uart_open_pty')
+}
+
+export async function uartClose(): Promise<void> {
+  await invoke('uart_close')
+}
+
+export async function uartStatus(): Promise<UartStatus> {
+  return await invoke('uart_status')
+}